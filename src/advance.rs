@@ -0,0 +1,303 @@
+use crate::app::RepeatMode;
+
+/// What moves playback along.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Trigger {
+    /// The playing track ended on its own.
+    Finished,
+    /// The user skipped forward.
+    Next,
+    /// The user went back.
+    Previous,
+}
+
+/// Where playback goes next.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Step {
+    /// Play the first entry of the user queue.
+    Queue,
+    /// Play this position of the play order.
+    Order(usize),
+    /// Play this library track, used to loop an album.
+    Track(usize),
+    /// Shuffle a fresh play order and start from its beginning.
+    Reshuffle,
+    /// Play the current track again from the start.
+    Restart,
+    /// Keep playing the current track.
+    Stay,
+    /// End playback.
+    Stop,
+}
+
+/// The state playback advances from. The play order and the user queue
+/// decide what comes next; library order never does.
+#[derive(Clone, Copy)]
+pub struct Position {
+    pub order_len: usize,
+    /// Position of the playing track in the play order, if it came from there.
+    pub order_index: Option<usize>,
+    /// Whether the user queue has entries.
+    pub queued: bool,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+    /// Previous and next track of the playing album, wrapping around.
+    pub album: Option<(usize, usize)>,
+}
+
+impl Position {
+    pub fn step(&self, trigger: Trigger) -> Step {
+        match trigger {
+            Trigger::Finished if self.repeat == RepeatMode::One => Step::Restart,
+            Trigger::Finished | Trigger::Next => self.forward(trigger),
+            Trigger::Previous => self.backward(),
+        }
+    }
+
+    /// Whether radio mode should queue a track before moving on: only when
+    /// going forward would otherwise run off the end of the play order.
+    pub fn wants_radio(&self, trigger: Trigger) -> bool {
+        trigger != Trigger::Previous && matches!(self.step(trigger), Step::Stop | Step::Stay)
+    }
+
+    fn forward(&self, trigger: Trigger) -> Step {
+        if self.queued {
+            return Step::Queue;
+        }
+        if self.repeat == RepeatMode::Album
+            && let Some((_, next)) = self.album
+        {
+            return Step::Track(next);
+        }
+        if self.order_len == 0 {
+            return Step::Stop;
+        }
+
+        let next = self.order_index.map_or(0, |i| i + 1);
+        if next < self.order_len {
+            Step::Order(next)
+        } else if self.repeat == RepeatMode::All {
+            if self.shuffle {
+                Step::Reshuffle
+            } else {
+                Step::Order(0)
+            }
+        } else if trigger == Trigger::Finished {
+            Step::Stop
+        } else {
+            Step::Stay
+        }
+    }
+
+    fn backward(&self) -> Step {
+        if self.repeat == RepeatMode::Album
+            && let Some((previous, _)) = self.album
+        {
+            return Step::Track(previous);
+        }
+
+        match self.order_index {
+            Some(i) if i > 0 && i <= self.order_len => Step::Order(i - 1),
+            Some(_) if self.repeat == RepeatMode::All && self.order_len > 0 => {
+                Step::Order(self.order_len - 1)
+            }
+            Some(_) => Step::Restart,
+            None => Step::Stay,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [RepeatMode; 4] = [
+        RepeatMode::Off,
+        RepeatMode::All,
+        RepeatMode::Album,
+        RepeatMode::One,
+    ];
+
+    fn position(order_index: Option<usize>, shuffle: bool, repeat: RepeatMode) -> Position {
+        Position {
+            order_len: 5,
+            order_index,
+            queued: false,
+            shuffle,
+            repeat,
+            album: None,
+        }
+    }
+
+    #[test]
+    fn finishing_mid_order_plays_the_next_entry() {
+        for shuffle in [false, true] {
+            for repeat in [RepeatMode::Off, RepeatMode::All, RepeatMode::Album] {
+                let pos = position(Some(2), shuffle, repeat);
+                assert_eq!(pos.step(Trigger::Finished), Step::Order(3));
+                assert_eq!(pos.step(Trigger::Next), Step::Order(3));
+            }
+        }
+    }
+
+    #[test]
+    fn finishing_the_last_entry_stops_without_repeat() {
+        for shuffle in [false, true] {
+            let pos = position(Some(4), shuffle, RepeatMode::Off);
+            assert_eq!(pos.step(Trigger::Finished), Step::Stop);
+        }
+    }
+
+    #[test]
+    fn skipping_past_the_last_entry_keeps_playing_without_repeat() {
+        for shuffle in [false, true] {
+            for repeat in [RepeatMode::Off, RepeatMode::One] {
+                let pos = position(Some(4), shuffle, repeat);
+                assert_eq!(pos.step(Trigger::Next), Step::Stay);
+            }
+        }
+    }
+
+    #[test]
+    fn repeat_all_wraps_and_reshuffles_when_shuffled() {
+        let pos = position(Some(4), false, RepeatMode::All);
+        assert_eq!(pos.step(Trigger::Finished), Step::Order(0));
+        assert_eq!(pos.step(Trigger::Next), Step::Order(0));
+
+        let pos = position(Some(4), true, RepeatMode::All);
+        assert_eq!(pos.step(Trigger::Finished), Step::Reshuffle);
+        assert_eq!(pos.step(Trigger::Next), Step::Reshuffle);
+    }
+
+    #[test]
+    fn repeat_one_restarts_on_finish_but_skips_on_next() {
+        for shuffle in [false, true] {
+            let mut pos = position(Some(1), shuffle, RepeatMode::One);
+            assert_eq!(pos.step(Trigger::Finished), Step::Restart);
+            assert_eq!(pos.step(Trigger::Next), Step::Order(2));
+            assert_eq!(pos.step(Trigger::Previous), Step::Order(0));
+
+            pos.queued = true;
+            assert_eq!(pos.step(Trigger::Finished), Step::Restart);
+            assert_eq!(pos.step(Trigger::Next), Step::Queue);
+        }
+    }
+
+    #[test]
+    fn user_queue_comes_before_the_play_order() {
+        for shuffle in [false, true] {
+            for repeat in [RepeatMode::Off, RepeatMode::All, RepeatMode::Album] {
+                let mut pos = position(Some(4), shuffle, repeat);
+                pos.queued = true;
+                pos.album = Some((0, 1));
+                assert_eq!(pos.step(Trigger::Finished), Step::Queue);
+                assert_eq!(pos.step(Trigger::Next), Step::Queue);
+            }
+        }
+    }
+
+    #[test]
+    fn previous_only_wraps_with_repeat_all() {
+        for shuffle in [false, true] {
+            for repeat in MODES {
+                let pos = position(Some(0), shuffle, repeat);
+                let expected = if repeat == RepeatMode::All {
+                    Step::Order(4)
+                } else {
+                    Step::Restart
+                };
+                assert_eq!(pos.step(Trigger::Previous), expected);
+                assert_eq!(
+                    position(Some(3), shuffle, repeat).step(Trigger::Previous),
+                    Step::Order(2)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn repeat_album_loops_within_the_album() {
+        for shuffle in [false, true] {
+            let mut pos = position(Some(4), shuffle, RepeatMode::Album);
+            pos.album = Some((7, 9));
+            assert_eq!(pos.step(Trigger::Finished), Step::Track(9));
+            assert_eq!(pos.step(Trigger::Next), Step::Track(9));
+            assert_eq!(pos.step(Trigger::Previous), Step::Track(7));
+        }
+    }
+
+    #[test]
+    fn album_neighbours_are_ignored_outside_repeat_album() {
+        for repeat in [RepeatMode::Off, RepeatMode::All] {
+            let mut pos = position(Some(1), false, repeat);
+            pos.album = Some((7, 9));
+            assert_eq!(pos.step(Trigger::Finished), Step::Order(2));
+            assert_eq!(pos.step(Trigger::Previous), Step::Order(0));
+        }
+    }
+
+    #[test]
+    fn nothing_played_from_the_order_starts_at_its_beginning() {
+        for shuffle in [false, true] {
+            for repeat in [RepeatMode::Off, RepeatMode::All, RepeatMode::Album] {
+                let pos = position(None, shuffle, repeat);
+                assert_eq!(pos.step(Trigger::Finished), Step::Order(0));
+                assert_eq!(pos.step(Trigger::Next), Step::Order(0));
+                assert_eq!(pos.step(Trigger::Previous), Step::Stay);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_order_stops() {
+        for shuffle in [false, true] {
+            for repeat in [RepeatMode::Off, RepeatMode::All, RepeatMode::Album] {
+                let mut pos = position(None, shuffle, repeat);
+                pos.order_len = 0;
+                assert_eq!(pos.step(Trigger::Finished), Step::Stop);
+                assert_eq!(pos.step(Trigger::Previous), Step::Stay);
+            }
+        }
+    }
+
+    #[test]
+    fn radio_fills_only_when_the_order_runs_out() {
+        for shuffle in [false, true] {
+            let pos = position(Some(4), shuffle, RepeatMode::Off);
+            assert!(pos.wants_radio(Trigger::Finished));
+            assert!(pos.wants_radio(Trigger::Next));
+            assert!(!pos.wants_radio(Trigger::Previous));
+
+            let mut empty = position(None, shuffle, RepeatMode::Off);
+            empty.order_len = 0;
+            assert!(empty.wants_radio(Trigger::Finished));
+
+            for trigger in [Trigger::Finished, Trigger::Next, Trigger::Previous] {
+                assert!(!position(Some(2), shuffle, RepeatMode::Off).wants_radio(trigger));
+                assert!(!position(Some(0), shuffle, RepeatMode::Off).wants_radio(trigger));
+            }
+        }
+    }
+
+    #[test]
+    fn radio_waits_while_the_queue_or_a_repeat_continues() {
+        let mut queued = position(Some(4), false, RepeatMode::Off);
+        queued.queued = true;
+        assert!(!queued.wants_radio(Trigger::Finished));
+        assert!(!queued.wants_radio(Trigger::Next));
+
+        for shuffle in [false, true] {
+            let pos = position(Some(4), shuffle, RepeatMode::All);
+            assert!(!pos.wants_radio(Trigger::Finished));
+            assert!(!pos.wants_radio(Trigger::Next));
+
+            let mut album = position(Some(4), shuffle, RepeatMode::Album);
+            album.album = Some((3, 5));
+            assert!(!album.wants_radio(Trigger::Finished));
+
+            let pos = position(Some(4), shuffle, RepeatMode::One);
+            assert!(!pos.wants_radio(Trigger::Finished));
+            assert!(pos.wants_radio(Trigger::Next));
+        }
+    }
+}
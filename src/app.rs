@@ -1,8 +1,29 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
 use rand::seq::{IteratorRandom, SliceRandom};
 use ratatui::widgets::ListState;
 
-use crate::player::{PlaybackState, Player};
+use crate::advance::{Position, Step, Trigger};
+use crate::collate::Collator;
+use crate::columns::{Column, MIN_WIDTH, SortSpec};
+use crate::config::Config;
+use crate::dsp::ChannelSettings;
+use crate::folders::{FolderRow, FolderTree};
+use crate::history::{History, PlayRecord, Stats, StatsPeriod, is_skip, now_secs};
+use crate::library::{AlbumNode, ArtistNode, Library, LibraryColumn};
+use crate::player::{PlaybackState, Player, level_to_gain};
+use crate::playlist::{Playlist, load_playlists};
+use crate::radio::pick_similar;
+use crate::ratings::{MAX_STARS, Rating, Ratings, write_rating_tag};
 use crate::scanner::Track;
+use crate::scrobble::{MIN_TRACK_SECS, ScrobbleLog, qualifies};
+use crate::search::TrackFilter;
+use crate::shuffle::{ShuffleMode, shuffled_order};
+use crate::smart::SmartRules;
+use crate::submit::{Listen, Submitter};
+use crate::undo::{PlaylistSnapshot, UndoStack};
 
 use serde::{Deserialize, Serialize};
 
@@ -10,6 +31,8 @@ use serde::{Deserialize, Serialize};
 pub enum RepeatMode {
     Off,
     All,
+    /// Loops the playing track's album in track order.
+    Album,
     One,
 }
 
@@ -19,41 +42,203 @@ impl Default for RepeatMode {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub enum SortMode {
-    Filename,
-    Title,
-    Artist,
+/// What a column key does after `o` or `O`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SortPick {
+    /// Sort by the column alone.
+    Replace,
+    /// Add the column as a further sort key.
+    Append,
 }
 
-impl Default for SortMode {
-    fn default() -> Self {
-        Self::Filename
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Playlist,
+    Library,
+    Folders,
+    Queue,
+    Playlists,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    NewPlaylist,
+    NewSmart,
+    Rename,
+    Import,
+    Export,
+    Search,
+    StopAfter,
+}
+
+impl PromptKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PromptKind::NewPlaylist => "New playlist name",
+            PromptKind::NewSmart => "Smart playlist (name: rule; rule)",
+            PromptKind::Rename => "Rename playlist to",
+            PromptKind::Import => "Import playlist from",
+            PromptKind::Export => "Export playlist to",
+            PromptKind::Search => "Search",
+            PromptKind::StopAfter => "Stop after how many tracks",
+        }
+    }
+}
+
+/// A play in progress. It is keyed by path so that re-sorting the library
+/// mid-track still credits the track that is actually playing.
+struct PlaySession {
+    path: PathBuf,
+    started_at: u64,
+    listened: std::time::Duration,
+    last_tick: Option<std::time::Instant>,
+    /// Whether the scrobbling service has been told about this play yet.
+    announced: bool,
+}
+
+impl PlaySession {
+    fn new(track: &Track) -> Self {
+        Self {
+            path: track.path.clone(),
+            started_at: now_secs(),
+            listened: std::time::Duration::ZERO,
+            last_tick: None,
+            announced: false,
+        }
+    }
+
+    /// Library index of the played track and its history entry, found by
+    /// path in `tracks` as currently sorted. Plays under a second give none.
+    fn finish(&self, tracks: &[Track], completed: bool) -> Option<(usize, PlayRecord)> {
+        if self.listened.as_secs() == 0 {
+            return None;
+        }
+        let index = tracks.iter().position(|t| t.path == self.path)?;
+        Some((index, self.record(&tracks[index], completed)))
+    }
+
+    /// History entry for this play of `track`.
+    fn record(&self, track: &Track, completed: bool) -> PlayRecord {
+        let listened_secs = self.listened.as_secs();
+        PlayRecord {
+            path: track.path.clone(),
+            artist: track.artist.clone(),
+            title: track.title.clone(),
+            album: track.album.clone(),
+            started_at: self.started_at,
+            listened_secs,
+            duration_secs: track.duration,
+            skipped: is_skip(listened_secs, track.duration, completed),
+        }
     }
 }
 
+/// Queue, play order and the edited playlists as restored by undo and redo.
+/// Tracks are kept as paths so the snapshot survives re-sorting.
+struct Snapshot {
+    queue: Vec<PathBuf>,
+    play_order: Vec<PathBuf>,
+    order_index: Option<usize>,
+    shuffle: bool,
+    shuffle_mode: ShuffleMode,
+    sort: SortSpec,
+    playlists: PlaylistSnapshot,
+}
+
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub text: String,
+}
+
 pub struct App {
+    pub config: Config,
     pub tracks: Vec<Track>,
     pub list_state: ListState,
     pub player: Player,
     pub playing_index: Option<usize>,
     pub running: bool,
     pub repeat_mode: RepeatMode,
+    /// Tracks left to finish, the playing one included, before playback stops.
+    pub stop_after: Option<u32>,
     pub shuffle: bool,
-    pub sort_mode: SortMode,
+    pub shuffle_mode: ShuffleMode,
+    /// Keeps the queue going with tracks similar to the one playing.
+    pub radio: bool,
+    pub sort: SortSpec,
+    /// Columns of the playlist view's track table.
+    pub columns: Vec<Column>,
+    /// Widths changed at runtime, taking precedence over the config.
+    pub column_widths: HashMap<Column, u16>,
+    /// Column that `<` and `>` resize.
+    pub focused_column: usize,
     pub show_help: bool,
     pub show_lyrics: bool,
     pub status_message: Option<(String, std::time::Instant)>,
+    pub view: View,
+    /// Library order used once the user queue is empty; shuffled when shuffle is on.
+    pub play_order: Vec<usize>,
+    pub order_index: Option<usize>,
+    /// Tracks the user asked to hear next, consumed from the front.
     pub queue: Vec<usize>,
-    pub queue_index: Option<usize>,
+    pub queue_state: ListState,
+    /// Library indices marked for batch actions in the playlist view.
+    pub marked_tracks: BTreeSet<usize>,
+    /// Queue positions marked for batch actions in the queue view.
+    pub marked_queue: BTreeSet<usize>,
+    /// Row where an active visual range selection started.
+    pub visual_anchor: Option<usize>,
+    /// Set after `*` while waiting for the 0-5 star rating.
+    pub pending_rating: bool,
+    /// Set after `o` or `O` while waiting for a column key.
+    pub pending_sort: Option<SortPick>,
+    ratings: Ratings,
+    pub playlists: Vec<Playlist>,
+    pub playlist_state: ListState,
+    /// Playlist whose entries are shown in the playlists view.
+    pub open_playlist: Option<usize>,
+    pub playlist_entry_state: ListState,
+    /// Playlist files from the music directory that were already imported.
+    pub imported_playlists: Vec<PathBuf>,
+    pub prompt: Option<Prompt>,
+    /// Search narrowing the playlist view; `list_state` indexes into its matches.
+    pub filter: Option<TrackFilter>,
+    pub library: Library,
+    pub library_column: LibraryColumn,
+    pub artist_state: ListState,
+    pub album_state: ListState,
+    pub library_track_state: ListState,
+    pub folder_tree: FolderTree,
+    pub folder_state: ListState,
+    pub history: History,
+    pub show_stats: bool,
+    pub stats: Option<Stats>,
+    pub stats_period: StatsPeriod,
+    /// Selection in the recent plays list of the statistics overlay.
+    pub history_state: ListState,
+    session: Option<PlaySession>,
+    scrobble_log: Option<ScrobbleLog>,
+    submitter: Option<Submitter>,
+    edits: UndoStack<Snapshot>,
+    collator: Collator,
+    track_lookup: HashMap<PathBuf, usize>,
 }
 
 use crate::state::AppState;
 
 impl App {
-    pub fn new(tracks: Vec<Track>) -> Self {
+    pub fn new(mut tracks: Vec<Track>, config: Config) -> Self {
         let state = AppState::load();
 
+        let ratings = Ratings::load();
+        let history = History::load();
+        let play_counts = history.play_counts();
+        for track in &mut tracks {
+            let rating = ratings.get(&track.path);
+            track.rating = rating.stars;
+            track.favorite = rating.favorite;
+            track.plays = play_counts.get(track.path.as_path()).copied().unwrap_or(0);
+        }
+
         let mut list_state = ListState::default();
         if !tracks.is_empty() {
             let initial_index = if let Some(path) = &state.last_track_path {
@@ -65,7 +250,16 @@ impl App {
         }
 
         let mut player = Player::new().expect("Failed to initialize audio player");
-        player.set_volume(state.volume);
+        player.volume_step = config.volume_step;
+        player.set_max_volume_db(config.max_volume_db);
+        player.set_volume(state.volume_level());
+        player.trim_silence = state.trim_silence;
+        player.silence_threshold = 10f32.powf(config.silence_threshold_db / 20.0);
+        player.set_channel_settings(ChannelSettings {
+            balance: state.balance,
+            mono: state.mono,
+            swap: state.swap_channels,
+        });
 
         let playing_index = if !tracks.is_empty() && state.last_track_path.is_some() {
             let idx = state
@@ -77,120 +271,435 @@ impl App {
             None
         };
 
-        let mut queue: Vec<usize> = (0..tracks.len()).collect();
-        let mut queue_index = None;
+        let positions: HashMap<&PathBuf, usize> = tracks
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (&t.path, i))
+            .collect();
 
-        if !tracks.is_empty() {
-            if let Some(path) = &state.last_track_path {
-                let current_index = tracks.iter().position(|t| &t.path == path);
+        let mut play_order: Vec<usize> = (0..tracks.len()).collect();
+        let mut order_index = None;
+        // Shuffle strategies need the library tree, so a fresh order is built once it exists.
+        let mut reshuffle = false;
 
-                if state.shuffle {
-                    let mut rng = rand::thread_rng();
-                    queue.shuffle(&mut rng);
-                    if let Some(idx) = current_index {
-                        queue_index = queue.iter().position(|&i| i == idx);
-                    }
-                } else {
-                    queue_index = current_index;
-                }
-            }
+        if state.shuffle && !state.play_order.is_empty() {
+            (play_order, order_index) =
+                restore_play_order(&state.play_order, state.order_index, &positions);
+        } else if state.shuffle {
+            reshuffle = true;
+        } else {
+            order_index = playing_index;
         }
 
-        Self {
+        let queue: Vec<usize> = state
+            .queue
+            .iter()
+            .filter_map(|path| positions.get(path).copied())
+            .collect();
+        let mut queue_state = ListState::default();
+        if !queue.is_empty() {
+            queue_state.select(Some(0));
+        }
+
+        let collator = Collator::new(&config);
+        let (playlists, playlist_errors) = load_playlists(&tracks, &collator);
+        let mut playlist_state = ListState::default();
+        if !playlists.is_empty() {
+            playlist_state.select(Some(0));
+        }
+
+        let music_dir = config.music_dir.clone();
+        let scrobble_log = ScrobbleLog::new(config.scrobbler_log.clone());
+        let columns = if config.columns.is_empty() {
+            Config::default().columns
+        } else {
+            config.columns.clone()
+        };
+        let submitter = config.scrobbling.clone().map(Submitter::start);
+        let mut app = Self {
+            config,
             tracks,
             list_state,
             player,
             playing_index,
             running: true,
             repeat_mode: state.repeat_mode,
+            stop_after: None,
             shuffle: state.shuffle,
-            sort_mode: state.sort_mode,
+            shuffle_mode: state.shuffle_mode,
+            radio: state.radio,
+            sort: state.sort(),
+            columns,
+            column_widths: state.column_widths,
+            focused_column: 0,
             show_help: false,
             show_lyrics: false,
             status_message: None,
+            view: View::Playlist,
+            play_order,
+            order_index,
             queue,
-            queue_index,
+            queue_state,
+            marked_tracks: BTreeSet::new(),
+            marked_queue: BTreeSet::new(),
+            visual_anchor: None,
+            pending_rating: false,
+            pending_sort: None,
+            ratings,
+            playlists,
+            playlist_state,
+            open_playlist: None,
+            playlist_entry_state: ListState::default(),
+            imported_playlists: state.imported_playlists,
+            prompt: None,
+            filter: None,
+            library: Library::default(),
+            library_column: LibraryColumn::Artists,
+            artist_state: ListState::default(),
+            album_state: ListState::default(),
+            library_track_state: ListState::default(),
+            folder_tree: FolderTree::build(&music_dir, &[]),
+            folder_state: ListState::default(),
+            history,
+            show_stats: false,
+            stats: None,
+            stats_period: StatsPeriod::Week,
+            history_state: ListState::default(),
+            session: None,
+            scrobble_log,
+            submitter,
+            edits: UndoStack::default(),
+            collator,
+            track_lookup: HashMap::new(),
+        };
+        app.rebuild_track_lookup();
+        app.rebuild_library();
+        app.rebuild_folders();
+        if reshuffle {
+            app.reshuffle();
+        }
+
+        if !playlist_errors.is_empty() {
+            app.set_status(format!(
+                "Skipped invalid smart playlist(s): {}",
+                playlist_errors.join(", ")
+            ));
+        }
+
+        if let Some(index) = app.playing_index {
+            app.resume_at(
+                index,
+                std::time::Duration::from_millis(state.last_position_ms),
+            );
+        }
+
+        app
+    }
+
+    /// Loads a track paused at `position`, as left by the previous session.
+    fn resume_at(&mut self, index: usize, position: std::time::Duration) {
+        if self.play_track(index) {
+            self.player.toggle_pause();
+            if !position.is_zero() {
+                self.seek_to(position);
+            }
+        } else {
+            self.playing_index = None;
         }
     }
 
     pub fn quit(&mut self) {
+        let last_position = if self.player.state == PlaybackState::Stopped {
+            std::time::Duration::ZERO
+        } else {
+            self.player.position()
+        };
+
+        self.finish_play(false);
         self.player.stop();
         self.running = false;
 
         let last_track_path = self.playing_index.map(|i| self.tracks[i].path.clone());
+        let track_paths = |indices: &[usize]| -> Vec<PathBuf> {
+            indices
+                .iter()
+                .map(|&i| self.tracks[i].path.clone())
+                .collect()
+        };
+        let channel_settings = self.player.channel_settings();
 
         let state = AppState {
-            volume: self.player.volume,
+            volume: level_to_gain(self.player.volume),
+            volume_level: Some(self.player.volume),
+            balance: channel_settings.balance,
+            mono: channel_settings.mono,
+            swap_channels: channel_settings.swap,
+            trim_silence: self.player.trim_silence,
             shuffle: self.shuffle,
+            shuffle_mode: self.shuffle_mode,
+            radio: self.radio,
             repeat_mode: self.repeat_mode,
-            sort_mode: self.sort_mode,
+            sort: Some(self.sort.clone()),
+            sort_mode: None,
+            column_widths: std::mem::take(&mut self.column_widths),
             last_track_path,
+            last_position_ms: last_position.as_millis() as u64,
+            queue: track_paths(&self.queue),
+            play_order: if self.shuffle {
+                track_paths(&self.play_order)
+            } else {
+                Vec::new()
+            },
+            order_index: self.order_index,
+            imported_playlists: std::mem::take(&mut self.imported_playlists),
         };
         state.save();
     }
 
-    pub fn selected(&self) -> usize {
-        self.list_state.selected().unwrap_or(0)
+    /// Library index of the track selected in the playlist view.
+    pub fn selected(&self) -> Option<usize> {
+        let row = self.list_state.selected()?;
+        match &self.filter {
+            Some(filter) => filter.matches.get(row).copied(),
+            None => Some(row).filter(|&i| i < self.tracks.len()),
+        }
+    }
+
+    /// Library indices shown in the playlist view, in display order.
+    pub fn visible_tracks(&self) -> Vec<usize> {
+        match &self.filter {
+            Some(filter) => filter.matches.clone(),
+            None => (0..self.tracks.len()).collect(),
+        }
+    }
+
+    /// Moves the playlist selection to a library track, if it is visible.
+    fn select_track(&mut self, index: usize) {
+        let row = match &self.filter {
+            Some(filter) => filter.matches.iter().position(|&i| i == index),
+            None => Some(index),
+        };
+        if row.is_some() {
+            self.list_state.select(row);
+        }
     }
 
     pub fn select_previous(&mut self) {
-        let current = self.selected();
+        let (state, _) = self.active_list();
+        let current = state.selected().unwrap_or(0);
         if current > 0 {
-            self.list_state.select(Some(current - 1));
+            state.select(Some(current - 1));
+            if self.view == View::Library {
+                self.library_selection_changed();
+            }
         }
     }
 
     pub fn select_next(&mut self) {
-        let current = self.selected();
-        if !self.tracks.is_empty() && current < self.tracks.len() - 1 {
-            self.list_state.select(Some(current + 1));
+        let (state, len) = self.active_list();
+        let current = state.selected().unwrap_or(0);
+        if len > 0 && current < len - 1 {
+            state.select(Some(current + 1));
+            if self.view == View::Library {
+                self.library_selection_changed();
+            }
+        }
+    }
+
+    fn active_list(&mut self) -> (&mut ListState, usize) {
+        match self.view {
+            View::Playlist => {
+                let len = self
+                    .filter
+                    .as_ref()
+                    .map_or(self.tracks.len(), |f| f.matches.len());
+                (&mut self.list_state, len)
+            }
+            View::Folders => {
+                let len = self.folder_tree.rows().len();
+                (&mut self.folder_state, len)
+            }
+            View::Library => match self.library_column {
+                LibraryColumn::Artists => (&mut self.artist_state, self.library.artists.len()),
+                LibraryColumn::Albums => {
+                    let len = self.library_artist().map_or(0, |a| a.albums.len());
+                    (&mut self.album_state, len)
+                }
+                LibraryColumn::Tracks => {
+                    let len = self.library_album().map_or(0, |a| a.tracks.len());
+                    (&mut self.library_track_state, len)
+                }
+            },
+            View::Queue => (&mut self.queue_state, self.queue.len()),
+            View::Playlists => match self.open_playlist {
+                Some(i) => (
+                    &mut self.playlist_entry_state,
+                    self.playlists[i].entries.len(),
+                ),
+                None => (&mut self.playlist_state, self.playlists.len()),
+            },
         }
     }
 
+    pub fn cycle_view(&mut self) {
+        self.view = match self.view {
+            View::Playlist => View::Library,
+            View::Library => View::Folders,
+            View::Folders => View::Queue,
+            View::Queue => View::Playlists,
+            View::Playlists => View::Playlist,
+        };
+        self.show_lyrics = false;
+        // Marks are rows of the view they were made in.
+        self.visual_anchor = None;
+        self.marked_tracks.clear();
+        self.marked_queue.clear();
+        self.clamp_queue_selection();
+    }
+
     pub fn play_selected(&mut self) {
-        if self.tracks.is_empty() {
-            return;
+        if let Some(index) = self.selected() {
+            self.play_index(index);
+        }
+    }
+
+    /// Plays a library track and moves the play order to it.
+    fn play_index(&mut self, index: usize) {
+        if self.play_track(index)
+            && let Some(pos) = self.play_order.iter().position(|&i| i == index)
+        {
+            self.order_index = Some(pos);
         }
+    }
 
-        let index = self.selected();
+    fn play_track(&mut self, index: usize) -> bool {
+        self.finish_play(false);
         let track = &self.tracks[index];
 
         match self.player.play(&track.path, &track.title) {
             Ok(_) => {
                 self.playing_index = Some(index);
-                if let Some(pos) = self.queue.iter().position(|&i| i == index) {
-                    self.queue_index = Some(pos);
-                }
+                self.start_session(index);
+                true
             }
             Err(e) => {
                 self.set_status(format!("Error: {}", e));
+                false
             }
         }
     }
 
-    pub fn play_next(&mut self) {
-        if self.tracks.is_empty() {
-            return;
+    /// Where playback stands, for deciding what plays next.
+    fn position(&self) -> Position {
+        Position {
+            order_len: self.play_order.len(),
+            order_index: self.order_index,
+            queued: !self.queue.is_empty(),
+            shuffle: self.shuffle,
+            repeat: self.repeat_mode,
+            album: self.album_neighbours(),
+        }
+    }
+
+    /// Previous and next track of the playing album while repeating the album.
+    fn album_neighbours(&self) -> Option<(usize, usize)> {
+        if self.repeat_mode != RepeatMode::Album {
+            return None;
         }
+        let current = self.playing_index?;
+        let tracks = &self.library.album_of(current)?.tracks;
+        let pos = tracks.iter().position(|&i| i == current)?;
+        let len = tracks.len();
+        Some((tracks[(pos + len - 1) % len], tracks[(pos + 1) % len]))
+    }
 
-        let current_q_idx = self.queue_index.unwrap_or(0);
-        let next_q_idx = current_q_idx + 1;
+    /// Track that plays when the current one finishes, without advancing anything.
+    pub fn peek_next(&self) -> Option<usize> {
+        match self.position().step(Trigger::Finished) {
+            Step::Queue => self.queue.first().copied(),
+            Step::Order(pos) => self.play_order.get(pos).copied(),
+            Step::Track(index) => Some(index),
+            Step::Restart => self.playing_index,
+            Step::Reshuffle | Step::Stay | Step::Stop => None,
+        }
+    }
 
-        let final_q_idx = if next_q_idx >= self.queue.len() {
-            if self.repeat_mode == RepeatMode::All {
-                0
-            } else {
-                return;
+    pub fn play_next(&mut self) {
+        self.advance(Trigger::Next);
+    }
+
+    fn advance(&mut self, trigger: Trigger) {
+        if self.radio && self.position().wants_radio(trigger) {
+            self.fill_radio();
+        }
+        match self.position().step(trigger) {
+            Step::Queue => {
+                let track_idx = self.queue.remove(0);
+                self.marked_queue = self
+                    .marked_queue
+                    .iter()
+                    .filter_map(|pos| pos.checked_sub(1))
+                    .collect();
+                self.clamp_queue_selection();
+                self.select_track(track_idx);
+                self.play_track(track_idx);
             }
-        } else {
-            next_q_idx
+            Step::Order(pos) => self.play_order_position(pos),
+            Step::Track(track_idx) => {
+                self.select_track(track_idx);
+                self.play_index(track_idx);
+            }
+            Step::Reshuffle => {
+                self.reshuffle();
+                // Avoid hearing the track that just ended twice in a row.
+                if self.play_order.len() > 1
+                    && self.play_order.first() == self.playing_index.as_ref()
+                {
+                    self.play_order.rotate_left(1);
+                }
+                self.play_order_position(0);
+            }
+            Step::Restart => {
+                if let Some(index) = self.playing_index {
+                    self.play_track(index);
+                }
+            }
+            Step::Stay => {}
+            Step::Stop => self.stop(),
+        }
+    }
+
+    /// Queues a track similar to the playing one for radio mode.
+    fn fill_radio(&mut self) {
+        if !self.queue.is_empty() {
+            return;
+        }
+        let Some(seed) = self.playing_index else {
+            return;
         };
 
-        self.queue_index = Some(final_q_idx);
-        let track_idx = self.queue[final_q_idx];
+        let mut exclude: HashSet<&Path> = self
+            .history
+            .recent()
+            .take(self.config.radio_window)
+            .map(|record| record.path.as_path())
+            .collect();
+        exclude.insert(self.tracks[seed].path.as_path());
+        if let Some(index) = pick_similar(seed, &self.tracks, &self.history, &exclude) {
+            self.queue.push(index);
+            self.clamp_queue_selection();
+        }
+    }
+
+    pub fn toggle_radio(&mut self) {
+        self.radio = !self.radio;
+    }
 
-        self.list_state.select(Some(track_idx));
-        self.play_selected();
+    fn play_order_position(&mut self, pos: usize) {
+        self.order_index = Some(pos);
+        let track_idx = self.play_order[pos];
+        self.select_track(track_idx);
+        self.play_track(track_idx);
     }
 
     pub fn toggle_pause(&mut self) {
@@ -206,15 +715,17 @@ impl App {
                 if self.shuffle {
                     let mut rng = rand::thread_rng();
                     let index = (0..self.tracks.len()).choose(&mut rng).unwrap_or(0);
-                    self.list_state.select(Some(index));
+                    self.select_track(index);
+                    self.play_index(index);
+                } else {
+                    self.play_selected();
                 }
-
-                self.play_selected();
             }
         }
     }
 
     pub fn stop(&mut self) {
+        self.finish_play(false);
         self.player.stop();
         self.playing_index = None;
     }
@@ -231,6 +742,31 @@ impl App {
         self.player.toggle_mute();
     }
 
+    pub fn adjust_balance(&mut self, right: bool) {
+        self.player.adjust_balance(if right { 0.1 } else { -0.1 });
+    }
+
+    pub fn toggle_mono(&mut self) {
+        self.player.toggle_mono();
+    }
+
+    pub fn toggle_swap_channels(&mut self) {
+        self.player.toggle_swap_channels();
+    }
+
+    pub fn toggle_trim_silence(&mut self) {
+        self.player.trim_silence = !self.player.trim_silence;
+        let state = if self.player.trim_silence {
+            "on"
+        } else {
+            "off"
+        };
+        self.set_status(format!(
+            "Silence trimming {} (applies from next track)",
+            state
+        ));
+    }
+
     pub fn seek_forward(&mut self) {
         self.seek_by(5);
     }
@@ -242,43 +778,61 @@ impl App {
     pub fn seek_by(&mut self, seconds: i64) {
         let current = self.player.position();
         if seconds > 0 {
-            let new_pos = current + std::time::Duration::from_secs(seconds as u64);
-            self.player.seek(new_pos);
-        } else {
-            let sub = seconds.abs() as u64;
-            if current.as_secs() > sub {
-                let new_pos = current - std::time::Duration::from_secs(sub);
-                self.player.seek(new_pos);
-            } else {
-                self.player.seek(std::time::Duration::ZERO);
+            let mut new_pos = current + std::time::Duration::from_secs(seconds as u64);
+            if let Some(end) = self.playback_end() {
+                new_pos = new_pos.min(end.saturating_sub(std::time::Duration::from_secs(1)));
+            }
+            if new_pos > current {
+                self.seek_to(new_pos);
             }
+        } else {
+            let sub = std::time::Duration::from_secs(seconds.unsigned_abs());
+            self.seek_to(current.saturating_sub(sub));
+        }
+    }
+
+    /// End of the audible part of the playing track, if known.
+    fn playback_end(&self) -> Option<std::time::Duration> {
+        let index = self.playing_index?;
+        let duration = self.tracks[index].duration;
+
+        match self.player.trim_window() {
+            Some((_, Some(end))) => Some(end),
+            _ if duration > 0 => Some(std::time::Duration::from_secs(duration)),
+            _ => None,
+        }
+    }
+
+    fn seek_to(&mut self, position: std::time::Duration) {
+        match self.player.seek(position) {
+            Err(e) => self.set_status(e),
+            Ok(()) if self.player.is_seeking() => self.set_status(format!(
+                "Decoder cannot seek; decoding up to {}:{:02}",
+                position.as_secs() / 60,
+                position.as_secs() % 60
+            )),
+            Ok(()) => {}
         }
     }
 
     pub fn seek_percentage(&mut self, percent: u8) {
         if let Some(index) = self.playing_index {
-            let duration = self.tracks[index].duration;
-            let secs = (duration as f64 * (percent as f64 / 100.0)) as u64;
-            self.player.seek(std::time::Duration::from_secs(secs));
+            let duration = std::time::Duration::from_secs(self.tracks[index].duration);
+            let (start, end) = match self.player.trim_window() {
+                Some((start, end)) => (start, end.unwrap_or(duration)),
+                None => (std::time::Duration::ZERO, duration),
+            };
+            let offset = end.saturating_sub(start).mul_f64(percent as f64 / 100.0);
+            self.seek_to(start + offset);
         }
     }
 
     pub fn play_previous_track(&mut self) {
         let position = self.player.position();
         if position.as_secs() > 3 {
-            self.player.seek(std::time::Duration::ZERO);
+            self.seek_to(std::time::Duration::ZERO);
         } else {
-            let current_q_idx = self.queue_index.unwrap_or(0);
-            let prev_q_idx = if current_q_idx > 0 {
-                current_q_idx - 1
-            } else {
-                self.queue.len() - 1
-            };
-
-            self.queue_index = Some(prev_q_idx);
-            let track_idx = self.queue[prev_q_idx];
-            self.list_state.select(Some(track_idx));
-            self.play_selected();
+            self.advance(Trigger::Previous);
         }
     }
 
@@ -287,120 +841,1679 @@ impl App {
     }
 
     pub fn check_playback(&mut self) {
+        if let Err(e) = self.player.poll_seek() {
+            self.set_status(e);
+        }
+        self.tick_session();
+        if self.player.is_finished() && self.session.is_some() {
+            self.finish_play(true);
+        }
         if self.player.is_finished() {
-            let current_index = self.playing_index.unwrap_or(0);
-            let is_last_track = current_index + 1 >= self.tracks.len();
-
-            if !self.shuffle && self.repeat_mode == RepeatMode::Off && is_last_track {
-                self.playing_index = None;
-                self.player.state = PlaybackState::Stopped;
-            } else {
-                if self.repeat_mode == RepeatMode::One {
-                    self.player.seek(std::time::Duration::ZERO);
-                    self.player.state = PlaybackState::Playing;
+            if let Some(left) = self.stop_after {
+                if left <= 1 {
+                    self.stop_after = None;
+                    self.stop();
                     return;
                 }
-                self.play_next();
+                self.stop_after = Some(left - 1);
+            }
+            self.advance(Trigger::Finished);
+        }
+    }
+
+    fn start_session(&mut self, index: usize) {
+        self.session = Some(PlaySession::new(&self.tracks[index]));
+    }
+
+    /// Adds the time since the last tick to the current play while it is audible.
+    fn tick_session(&mut self) {
+        let playing = self.player.state == PlaybackState::Playing;
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        let now = std::time::Instant::now();
+        if playing && let Some(last) = session.last_tick {
+            session.listened += now - last;
+        }
+        session.last_tick = playing.then_some(now);
+
+        if playing && !session.announced {
+            session.announced = true;
+            if let Some(submitter) = &self.submitter
+                && let Some(&index) = self.track_lookup.get(&session.path)
+            {
+                let track = &self.tracks[index];
+                submitter.now_playing(Listen::new(track, session.started_at));
+            }
+        }
+    }
+
+    /// Ends the current play and appends it to the history and the scrobbler
+    /// log. Plays shorter than a second are dropped.
+    fn finish_play(&mut self, completed: bool) {
+        self.tick_session();
+        let Some(session) = self.session.take() else {
+            return;
+        };
+        let Some((index, record)) = session.finish(&self.tracks, completed) else {
+            return;
+        };
+        let listened_secs = record.listened_secs;
+        if !record.skipped {
+            self.tracks[index].plays += 1;
+            self.refresh_smart_playlists();
+        }
+        self.history.record(record);
+        let track = &self.tracks[index];
+
+        if qualifies(listened_secs, track.duration)
+            && let Some(submitter) = &self.submitter
+        {
+            submitter.scrobble(Listen::new(track, session.started_at));
+        }
+        if track.duration > MIN_TRACK_SECS
+            && let Some(log) = &self.scrobble_log
+            && let Err(e) = log.append(
+                track,
+                session.started_at,
+                qualifies(listened_secs, track.duration),
+            )
+        {
+            self.set_status(e);
+        }
+        if self.show_stats {
+            self.refresh_stats();
+        }
+    }
+
+    pub fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+        if self.show_stats {
+            self.show_help = false;
+            self.show_lyrics = false;
+            self.refresh_stats();
+            self.history_state.select(Some(0));
+        }
+    }
+
+    pub fn cycle_stats_period(&mut self) {
+        self.stats_period = self.stats_period.next();
+        self.refresh_stats();
+    }
+
+    fn refresh_stats(&mut self) {
+        self.stats = Some(self.history.stats(self.stats_period));
+        let len = self.history.records.len();
+        match self.history_state.selected() {
+            _ if len == 0 => self.history_state.select(None),
+            Some(i) if i >= len => self.history_state.select(Some(len - 1)),
+            None => self.history_state.select(Some(0)),
+            _ => {}
+        }
+    }
+
+    pub fn select_history(&mut self, down: bool) {
+        let len = self.history.records.len();
+        if len == 0 {
+            return;
+        }
+        let i = self.history_state.selected().unwrap_or(0);
+        let next = if down {
+            (i + 1).min(len - 1)
+        } else {
+            i.saturating_sub(1)
+        };
+        self.history_state.select(Some(next));
+    }
+
+    /// Plays the track selected in the recent plays list again.
+    pub fn replay_history_selected(&mut self) {
+        let Some(record) = self
+            .history_state
+            .selected()
+            .and_then(|i| self.history.recent().nth(i))
+        else {
+            return;
+        };
+        match self.track_index(&record.path) {
+            Some(index) => {
+                self.select_track(index);
+                self.play_index(index);
             }
+            None => self.set_status(format!("Not in library: {}", record.display_name())),
         }
     }
 
     pub fn check_repeat_mode(&mut self) {
         self.repeat_mode = match self.repeat_mode {
             RepeatMode::Off => RepeatMode::All,
-            RepeatMode::All => RepeatMode::One,
+            RepeatMode::All => RepeatMode::Album,
+            RepeatMode::Album => RepeatMode::One,
             RepeatMode::One => RepeatMode::Off,
         };
     }
 
+    pub fn toggle_stop_after_current(&mut self) {
+        if self.stop_after == Some(1) {
+            self.stop_after = None;
+            self.set_status("Stop after current track cancelled".to_string());
+        } else {
+            self.stop_after = Some(1);
+            self.set_status("Stopping after the current track".to_string());
+        }
+    }
+
     pub fn toggle_shuffle(&mut self) {
+        self.checkpoint(if self.shuffle {
+            "shuffle off"
+        } else {
+            "shuffle on"
+        });
         self.shuffle = !self.shuffle;
 
         if self.shuffle {
-            let mut rng = rand::thread_rng();
-            self.queue.shuffle(&mut rng);
-
-            if let Some(current_idx) = self.playing_index {
-                if let Some(pos) = self.queue.iter().position(|&i| i == current_idx) {
-                    self.queue_index = Some(pos);
-                }
-            }
+            self.reshuffle();
         } else {
-            self.queue = (0..self.tracks.len()).collect();
-            self.queue_index = self.playing_index;
+            self.play_order = (0..self.tracks.len()).collect();
+            self.order_index = self.playing_index;
         }
     }
 
-    pub fn toggle_help(&mut self) {
-        self.show_help = !self.show_help;
-        if self.show_help {
-            self.show_lyrics = false;
+    pub fn cycle_shuffle_mode(&mut self) {
+        self.checkpoint("shuffle strategy change");
+        self.shuffle_mode = self.shuffle_mode.next();
+        if self.shuffle {
+            self.reshuffle();
         }
+        self.set_status(format!("Shuffle: {}", self.shuffle_mode.label()));
     }
 
-    pub fn toggle_lyrics(&mut self) {
-        self.show_lyrics = !self.show_lyrics;
-        if self.show_lyrics {
-            self.show_help = false;
-        }
+    /// Builds a new play order with the current shuffle strategy, continuing
+    /// from the playing track.
+    fn reshuffle(&mut self) {
+        self.play_order = shuffled_order(
+            self.shuffle_mode,
+            &self.tracks,
+            &self.library,
+            &self.history.last_played(),
+        );
+        self.order_index = self
+            .playing_index
+            .and_then(|current| self.play_order.iter().position(|&i| i == current));
     }
 
-    pub fn cycle_sort_mode(&mut self) {
-        self.sort_mode = match self.sort_mode {
-            SortMode::Filename => SortMode::Title,
-            SortMode::Title => SortMode::Artist,
-            SortMode::Artist => SortMode::Filename,
-        };
-        self.sort_tracks();
+    pub fn enqueue_selected(&mut self) {
+        let tracks = self.batch_tracks();
+        self.enqueue_tracks(tracks, false);
+        self.clear_marks();
     }
 
-    pub fn sort_tracks(&mut self) {
-        let current_track_path = self.playing_index.map(|i| self.tracks[i].path.clone());
+    pub fn enqueue_selected_next(&mut self) {
+        let tracks = self.batch_tracks();
+        self.enqueue_tracks(tracks, true);
+        self.clear_marks();
+    }
 
-        match self.sort_mode {
-            SortMode::Filename => self.tracks.sort_by(|a, b| a.path.cmp(&b.path)),
-            SortMode::Title => self
-                .tracks
-                .sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase())),
-            SortMode::Artist => self
-                .tracks
-                .sort_by(|a, b| a.artist.to_lowercase().cmp(&b.artist.to_lowercase())),
+    pub fn queue_selected(&self) -> Option<usize> {
+        self.queue_state
+            .selected()
+            .filter(|&pos| pos < self.queue.len())
+    }
+
+    /// Plays the selected queue entries, moving the rest of them to the front.
+    pub fn play_queue_selected(&mut self) {
+        let positions = self.batch_queue_positions();
+        if positions.is_empty() {
+            return;
         }
 
-        if let Some(path) = current_track_path {
-            self.playing_index = self.tracks.iter().position(|t| t.path == path);
+        let mut tracks: Vec<usize> = positions.iter().map(|&pos| self.queue[pos]).collect();
+        for &pos in positions.iter().rev() {
+            self.queue.remove(pos);
         }
+        let track_idx = tracks.remove(0);
+        self.queue.splice(0..0, tracks);
+        self.clear_marks();
+        self.clamp_queue_selection();
+        self.select_track(track_idx);
+        self.play_track(track_idx);
+    }
 
-        if self.shuffle {
-            let mut rng = rand::thread_rng();
-            let mut new_queue: Vec<usize> = (0..self.tracks.len()).collect();
-            new_queue.shuffle(&mut rng);
-            self.queue = new_queue;
-
-            if let Some(current_idx) = self.playing_index {
-                if let Some(pos) = self.queue.iter().position(|&i| i == current_idx) {
-                    self.queue_index = Some(pos);
-                }
+    pub fn move_queue_selected(&mut self, up: bool) {
+        if let Some(pos) = self.queue_selected() {
+            let target = if up {
+                pos.checked_sub(1)
+            } else {
+                Some(pos + 1).filter(|&p| p < self.queue.len())
+            };
+            if let Some(target) = target {
+                self.checkpoint("move in queue");
+                self.queue.swap(pos, target);
+                self.queue_state.select(Some(target));
+                self.marked_queue.clear();
             }
+        }
+    }
+
+    pub fn remove_queue_selected(&mut self) {
+        let positions = self.batch_queue_positions();
+        if !positions.is_empty() {
+            self.checkpoint("remove from queue");
+        }
+        for &pos in positions.iter().rev() {
+            self.queue.remove(pos);
+        }
+        if positions.len() > 1 {
+            self.set_status(format!("Removed {} tracks from queue", positions.len()));
+        }
+        self.clear_marks();
+        self.clamp_queue_selection();
+    }
+
+    pub fn clear_queue(&mut self) {
+        if !self.queue.is_empty() {
+            self.checkpoint("clear queue");
+        }
+        self.queue.clear();
+        self.marked_queue.clear();
+        self.clamp_queue_selection();
+        self.set_status("Queue cleared".to_string());
+    }
+
+    fn clamp_queue_selection(&mut self) {
+        if self.queue.is_empty() {
+            self.queue_state.select(None);
         } else {
-            self.queue = (0..self.tracks.len()).collect();
-            self.queue_index = self.playing_index;
+            let pos = self.queue_state.selected().unwrap_or(0);
+            self.queue_state.select(Some(pos.min(self.queue.len() - 1)));
         }
+    }
 
-        if self.list_state.selected().is_none() && !self.tracks.is_empty() {
-            self.list_state.select(Some(0));
+    /// Starts a visual range at the cursor, or marks the range and ends it.
+    pub fn toggle_visual(&mut self) {
+        let cursor = match self.view {
+            View::Playlist => self.list_state.selected(),
+            View::Queue => self.queue_selected(),
+            _ => None,
+        };
+
+        if self.visual_anchor.is_none() {
+            self.visual_anchor = cursor;
+            return;
+        }
+
+        match self.view {
+            View::Playlist => {
+                let tracks = self.batch_tracks();
+                self.marked_tracks.extend(tracks);
+            }
+            View::Queue => {
+                let positions = self.batch_queue_positions();
+                self.marked_queue.extend(positions);
+            }
+            _ => {}
         }
+        self.visual_anchor = None;
     }
 
-    pub fn set_status(&mut self, message: String) {
-        self.status_message = Some((message, std::time::Instant::now()));
+    /// Marks or unmarks the item under the cursor and moves down.
+    pub fn toggle_mark(&mut self) {
+        match self.view {
+            View::Playlist => {
+                if let Some(index) = self.selected()
+                    && !self.marked_tracks.remove(&index)
+                {
+                    self.marked_tracks.insert(index);
+                }
+            }
+            View::Queue => {
+                if let Some(pos) = self.queue_selected()
+                    && !self.marked_queue.remove(&pos)
+                {
+                    self.marked_queue.insert(pos);
+                }
+            }
+            _ => return,
+        }
+        self.select_next();
     }
 
-    pub fn check_status_message(&mut self) {
-        if let Some((_, time)) = &self.status_message {
-            if time.elapsed().as_secs() > 3 {
-                self.status_message = None;
+    pub fn has_marks(&self) -> bool {
+        self.visual_anchor.is_some()
+            || match self.view {
+                View::Playlist => !self.marked_tracks.is_empty(),
+                View::Queue => !self.marked_queue.is_empty(),
+                _ => false,
             }
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.visual_anchor = None;
+        match self.view {
+            View::Playlist => self.marked_tracks.clear(),
+            View::Queue => self.marked_queue.clear(),
+            _ => {}
         }
     }
+
+    /// Rows covered by the active visual range in the current view.
+    pub fn visual_range(&self) -> Option<RangeInclusive<usize>> {
+        let anchor = self.visual_anchor?;
+        let cursor = match self.view {
+            View::Playlist => self.list_state.selected(),
+            View::Queue => self.queue_state.selected(),
+            _ => None,
+        }?;
+        Some(anchor.min(cursor)..=anchor.max(cursor))
+    }
+
+    /// Library indices track actions apply to: the marked and visual rows in
+    /// display order, or just the selected track. In the queue view these are
+    /// the tracks at the queue positions actions apply to.
+    pub fn batch_tracks(&self) -> Vec<usize> {
+        if self.view == View::Queue {
+            return self
+                .batch_queue_positions()
+                .into_iter()
+                .map(|pos| self.queue[pos])
+                .collect();
+        }
+
+        let range = self.visual_range();
+        let tracks: Vec<usize> = self
+            .visible_tracks()
+            .into_iter()
+            .enumerate()
+            .filter(|(row, index)| {
+                self.marked_tracks.contains(index)
+                    || range.as_ref().is_some_and(|r| r.contains(row))
+            })
+            .map(|(_, index)| index)
+            .collect();
+
+        if tracks.is_empty() {
+            self.selected().into_iter().collect()
+        } else {
+            tracks
+        }
+    }
+
+    /// Queue positions queue-view actions apply to, in ascending order.
+    pub fn batch_queue_positions(&self) -> Vec<usize> {
+        let mut positions = self.marked_queue.clone();
+        if let Some(range) = self.visual_range() {
+            positions.extend(range);
+        }
+        positions.retain(|&pos| pos < self.queue.len());
+
+        if positions.is_empty() {
+            self.queue_selected().into_iter().collect()
+        } else {
+            positions.into_iter().collect()
+        }
+    }
+
+    /// Number and combined duration in seconds of the marked items, if any.
+    pub fn marked_summary(&self) -> Option<(usize, u64)> {
+        if !self.has_marks() {
+            return None;
+        }
+        let tracks = match self.view {
+            View::Playlist | View::Queue => self.batch_tracks(),
+            _ => return None,
+        };
+        let duration = tracks.iter().map(|&i| self.tracks[i].duration).sum();
+        Some((tracks.len(), duration))
+    }
+
+    /// Plays the marked tracks, queueing all but the first, or the selected track.
+    pub fn play_marked(&mut self) {
+        if self.has_marks() {
+            let tracks = self.batch_tracks();
+            self.clear_marks();
+            self.play_tracks(tracks);
+        } else {
+            self.play_selected();
+        }
+    }
+
+    /// Waits for a digit rating the selected (or marked) tracks.
+    pub fn start_rating(&mut self) {
+        if self.selected().is_some() {
+            self.pending_rating = true;
+            self.set_status(format!("Rate: press 0-{}", MAX_STARS));
+        }
+    }
+
+    pub fn rate_selected(&mut self, stars: u8) {
+        self.pending_rating = false;
+        let stars = stars.min(MAX_STARS);
+        let tracks = self.batch_tracks();
+        for &index in &tracks {
+            self.tracks[index].rating = stars;
+        }
+        let message = format!(
+            "Rated {}: {}",
+            self.describe_tracks(&tracks),
+            "★".repeat(stars as usize)
+        );
+        self.store_ratings(&tracks, true, message);
+    }
+
+    pub fn toggle_favorite(&mut self) {
+        let tracks = self.batch_tracks();
+        let Some(&first) = tracks.first() else {
+            return;
+        };
+
+        let favorite = !self.tracks[first].favorite;
+        for &index in &tracks {
+            self.tracks[index].favorite = favorite;
+        }
+        let message = format!(
+            "{}: {}",
+            if favorite {
+                "♥ Added to favorites"
+            } else {
+                "Removed from favorites"
+            },
+            self.describe_tracks(&tracks)
+        );
+        self.store_ratings(&tracks, false, message);
+    }
+
+    fn describe_tracks(&self, tracks: &[usize]) -> String {
+        match tracks {
+            [index] => self.tracks[*index].display_name(),
+            _ => format!("{} tracks", tracks.len()),
+        }
+    }
+
+    /// Saves ratings and favorite flags, writing star ratings to tags when configured.
+    fn store_ratings(&mut self, tracks: &[usize], stars_changed: bool, message: String) {
+        let mut errors = Vec::new();
+        for &index in tracks {
+            let track = &self.tracks[index];
+            self.ratings.set(
+                &track.path,
+                Rating {
+                    stars: track.rating,
+                    favorite: track.favorite,
+                },
+            );
+
+            if stars_changed
+                && self.config.write_rating_tags
+                && let Err(e) = write_rating_tag(&track.path, track.rating)
+            {
+                errors.push(e);
+            }
+        }
+        self.ratings.save();
+        self.clear_marks();
+        self.refresh_smart_playlists();
+
+        match errors.first() {
+            Some(e) => self.set_status(format!("{}\n{}", message, e)),
+            None => self.set_status(message),
+        }
+    }
+
+    /// Captures the current state, including the named playlists.
+    fn snapshot(&self, playlists: &[String]) -> Snapshot {
+        let paths = |indices: &[usize]| -> Vec<PathBuf> {
+            indices
+                .iter()
+                .map(|&i| self.tracks[i].path.clone())
+                .collect()
+        };
+        Snapshot {
+            queue: paths(&self.queue),
+            play_order: paths(&self.play_order),
+            order_index: self.order_index,
+            shuffle: self.shuffle,
+            shuffle_mode: self.shuffle_mode,
+            sort: self.sort.clone(),
+            playlists: PlaylistSnapshot::take(&self.playlists, playlists),
+        }
+    }
+
+    /// Records the state before an edit so `undo` can bring it back.
+    fn checkpoint(&mut self, description: &str) {
+        self.checkpoint_playlists(description, &[]);
+    }
+
+    /// Like `checkpoint`, for an edit that also changes, adds or removes
+    /// the named playlists.
+    fn checkpoint_playlists(&mut self, description: &str, playlists: &[String]) {
+        let snapshot = self.snapshot(playlists);
+        self.edits.record(description, snapshot);
+    }
+
+    pub fn undo(&mut self) {
+        let names = self
+            .edits
+            .next_undo()
+            .map(|s| s.playlists.names())
+            .unwrap_or_default();
+        let current = self.snapshot(&names);
+        match self.edits.undo(current) {
+            Some((description, snapshot)) => {
+                self.restore(snapshot);
+                self.set_status(format!("Undone: {}", description));
+            }
+            None => self.set_status("Nothing to undo".to_string()),
+        }
+    }
+
+    pub fn redo(&mut self) {
+        let names = self
+            .edits
+            .next_redo()
+            .map(|s| s.playlists.names())
+            .unwrap_or_default();
+        let current = self.snapshot(&names);
+        match self.edits.redo(current) {
+            Some((description, snapshot)) => {
+                self.restore(snapshot);
+                self.set_status(format!("Redone: {}", description));
+            }
+            None => self.set_status("Nothing to redo".to_string()),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        if snapshot.sort != self.sort {
+            self.sort = snapshot.sort;
+            self.sort_tracks();
+        }
+        self.shuffle = snapshot.shuffle;
+        self.shuffle_mode = snapshot.shuffle_mode;
+        self.queue = snapshot
+            .queue
+            .iter()
+            .filter_map(|path| self.track_index(path))
+            .collect();
+        let play_order: Vec<usize> = snapshot
+            .play_order
+            .iter()
+            .filter_map(|path| self.track_index(path))
+            .collect();
+        // Playback has usually moved on since the edit; only a changed order
+        // needs the playing track found again.
+        if play_order != self.play_order {
+            self.order_index = self
+                .playing_index
+                .and_then(|index| play_order.iter().position(|&i| i == index))
+                .or(snapshot.order_index)
+                .filter(|&pos| pos < play_order.len());
+            self.play_order = play_order;
+        }
+        self.marked_queue.clear();
+        self.clamp_queue_selection();
+
+        let name_at = |index: Option<usize>| index.map(|i| self.playlists[i].name.clone());
+        let open_name = name_at(self.open_playlist);
+        let selected_name = name_at(self.selected_playlist());
+
+        let names = snapshot.playlists.names();
+        let replaced = snapshot.playlists.restore(&mut self.playlists);
+        for playlist in &replaced {
+            if !self.playlists.contains(playlist) {
+                playlist.delete().ok();
+            }
+        }
+        for index in 0..self.playlists.len() {
+            if names.contains(&self.playlists[index].name)
+                && !replaced.contains(&self.playlists[index])
+            {
+                self.save_playlist(index);
+            }
+        }
+
+        let find = |name: Option<String>| {
+            name.and_then(|name| self.playlists.iter().position(|p| p.name == name))
+        };
+        let (open, selected) = (find(open_name), find(selected_name));
+        match open {
+            Some(index) => self.open_playlist = Some(index),
+            None => self.close_playlist(),
+        }
+        if let Some(index) = selected {
+            self.playlist_state.select(Some(index));
+        }
+        let len = self.playlists.len();
+        if let Some(index) = self.open_playlist {
+            let entries = self.playlists[index].entries.len();
+            let pos = self.playlist_entry_state.selected().unwrap_or(0);
+            self.playlist_entry_state
+                .select((entries > 0).then(|| pos.min(entries - 1)));
+        }
+        let pos = self.playlist_state.selected().unwrap_or(0);
+        self.playlist_state
+            .select((len > 0).then(|| pos.min(len - 1)));
+    }
+
+    pub fn toggle_help(&mut self) {
+        self.show_help = !self.show_help;
+        if self.show_help {
+            self.show_lyrics = false;
+            self.show_stats = false;
+        }
+    }
+
+    pub fn toggle_lyrics(&mut self) {
+        self.show_lyrics = !self.show_lyrics;
+        if self.show_lyrics {
+            self.show_help = false;
+            self.show_stats = false;
+        }
+    }
+
+    pub fn start_sort(&mut self, pick: SortPick) {
+        self.pending_sort = Some(pick);
+        let keys: String = Column::ALL
+            .iter()
+            .map(|column| format!("{} {}", column.key(), column.label()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let action = match pick {
+            SortPick::Replace => "Sort by",
+            SortPick::Append => "Then by",
+        };
+        self.set_status(format!("{}: {}", action, keys));
+    }
+
+    /// Applies a column key pressed after `o` or `O`.
+    pub fn pick_sort_column(&mut self, key: char) {
+        let Some(pick) = self.pending_sort.take() else {
+            return;
+        };
+        let Some(column) = Column::from_key(key) else {
+            self.set_status(format!("No column for key: {}", key));
+            return;
+        };
+        self.checkpoint("sort change");
+        match pick {
+            SortPick::Replace => self.sort.sort_by(column),
+            SortPick::Append => self.sort.then_by(column),
+        }
+        self.sort_tracks();
+        self.set_status(format!("Sort: {}", self.sort.describe()));
+    }
+
+    pub fn column_width(&self, column: Column) -> u16 {
+        self.column_widths
+            .get(&column)
+            .or_else(|| self.config.column_widths.get(&column))
+            .copied()
+            .unwrap_or_else(|| column.default_width())
+    }
+
+    pub fn focus_column(&mut self, delta: isize) {
+        let len = self.columns.len() as isize;
+        if len > 0 {
+            self.focused_column = (self.focused_column as isize + delta).rem_euclid(len) as usize;
+        }
+    }
+
+    /// Widens or narrows the focused column.
+    pub fn resize_column(&mut self, delta: i16) {
+        let Some(&column) = self.columns.get(self.focused_column) else {
+            return;
+        };
+        let width = self
+            .column_width(column)
+            .saturating_add_signed(delta)
+            .max(MIN_WIDTH);
+        self.column_widths.insert(column, width);
+    }
+
+    pub fn sort_tracks(&mut self) {
+        let current_track_path = self.playing_index.map(|i| self.tracks[i].path.clone());
+        let selected_path = self.selected().map(|i| self.tracks[i].path.clone());
+        let queued_paths: Vec<PathBuf> = self
+            .queue
+            .iter()
+            .map(|&i| self.tracks[i].path.clone())
+            .collect();
+        let order_paths: Vec<PathBuf> = self
+            .play_order
+            .iter()
+            .map(|&i| self.tracks[i].path.clone())
+            .collect();
+        let marked_paths: Vec<PathBuf> = self
+            .marked_tracks
+            .iter()
+            .map(|&i| self.tracks[i].path.clone())
+            .collect();
+
+        let (sort, collator) = (&self.sort, &self.collator);
+        self.tracks.sort_by(|a, b| sort.compare(a, b, collator));
+
+        if let Some(path) = current_track_path {
+            self.playing_index = self.tracks.iter().position(|t| t.path == path);
+        }
+        self.rebuild_track_lookup();
+
+        let positions: HashMap<&PathBuf, usize> = self
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (&t.path, i))
+            .collect();
+        self.queue = queued_paths
+            .iter()
+            .filter_map(|path| positions.get(path).copied())
+            .collect();
+        self.marked_tracks = marked_paths
+            .iter()
+            .filter_map(|path| positions.get(path).copied())
+            .collect();
+        self.visual_anchor = None;
+
+        if self.shuffle {
+            (self.play_order, self.order_index) =
+                restore_play_order(&order_paths, self.order_index, &positions);
+        } else {
+            self.play_order = (0..self.tracks.len()).collect();
+            self.order_index = self.playing_index;
+        }
+
+        if let Some(filter) = &self.filter {
+            self.filter = Some(TrackFilter::new(&filter.query, &self.tracks));
+        }
+        self.rebuild_library();
+        self.rebuild_folders();
+        if let Some(index) = selected_path.and_then(|path| self.track_index(&path)) {
+            self.select_track(index);
+        }
+
+        if self.list_state.selected().is_none() && !self.visible_tracks().is_empty() {
+            self.list_state.select(Some(0));
+        }
+    }
+
+    /// Regroups the library tree, keeping the selected artist and album.
+    fn rebuild_library(&mut self) {
+        let artist = self.library_artist().map(|a| a.name.clone());
+        let album = self.library_album().map(|a| a.name.to_lowercase());
+
+        self.library = Library::build(&self.tracks, &self.collator);
+
+        let artist_index = artist.and_then(|name| self.library.find_artist(&name)).or(
+            if self.library.artists.is_empty() {
+                None
+            } else {
+                Some(0)
+            },
+        );
+        self.artist_state.select(artist_index);
+
+        let album_index = album.and_then(|name| {
+            self.library_artist()?
+                .albums
+                .iter()
+                .position(|a| a.name.to_lowercase() == name)
+        });
+        self.album_state.select(album_index);
+        self.library_track_state.select(None);
+        self.clamp_library_selection();
+    }
+
+    pub fn library_artist(&self) -> Option<&ArtistNode> {
+        self.library.artists.get(self.artist_state.selected()?)
+    }
+
+    pub fn library_album(&self) -> Option<&AlbumNode> {
+        self.library_artist()?
+            .albums
+            .get(self.album_state.selected()?)
+    }
+
+    /// Resets the columns right of the focused one after its selection moved.
+    fn library_selection_changed(&mut self) {
+        match self.library_column {
+            LibraryColumn::Artists => {
+                self.album_state.select(None);
+                self.library_track_state.select(None);
+            }
+            LibraryColumn::Albums => self.library_track_state.select(None),
+            LibraryColumn::Tracks => {}
+        }
+        self.clamp_library_selection();
+    }
+
+    /// Keeps album and track selections within the selected parent.
+    fn clamp_library_selection(&mut self) {
+        let album_count = self.library_artist().map_or(0, |a| a.albums.len());
+        let album = self.album_state.selected().unwrap_or(0);
+        self.album_state.select(if album_count == 0 {
+            None
+        } else {
+            Some(album.min(album_count - 1))
+        });
+
+        let track_count = self.library_album().map_or(0, |a| a.tracks.len());
+        let track = self.library_track_state.selected().unwrap_or(0);
+        self.library_track_state.select(if track_count == 0 {
+            None
+        } else {
+            Some(track.min(track_count - 1))
+        });
+    }
+
+    /// Moves focus between the artist, album and track columns.
+    pub fn library_move_column(&mut self, right: bool) {
+        self.library_column = match (self.library_column, right) {
+            (LibraryColumn::Artists, true) => LibraryColumn::Albums,
+            (LibraryColumn::Albums, true) => LibraryColumn::Tracks,
+            (LibraryColumn::Albums, false) => LibraryColumn::Artists,
+            (LibraryColumn::Tracks, false) => LibraryColumn::Albums,
+            (column, _) => column,
+        };
+    }
+
+    /// Tracks under the selection in the focused library column.
+    fn library_selection_tracks(&self) -> Vec<usize> {
+        match self.library_column {
+            LibraryColumn::Artists => self
+                .library_artist()
+                .map(|a| a.tracks())
+                .unwrap_or_default(),
+            LibraryColumn::Albums => self
+                .library_album()
+                .map(|a| a.tracks.clone())
+                .unwrap_or_default(),
+            LibraryColumn::Tracks => {
+                let pos = self.library_track_state.selected().unwrap_or(0);
+                self.library_album()
+                    .and_then(|a| a.tracks.get(pos))
+                    .map(|&i| vec![i])
+                    .unwrap_or_default()
+            }
+        }
+    }
+
+    /// Plays the selected artist or album, or the album from the selected track on.
+    pub fn play_library_selection(&mut self) {
+        let tracks = match self.library_column {
+            LibraryColumn::Tracks => {
+                let pos = self.library_track_state.selected().unwrap_or(0);
+                self.library_album()
+                    .map(|a| a.tracks[pos.min(a.tracks.len())..].to_vec())
+                    .unwrap_or_default()
+            }
+            _ => self.library_selection_tracks(),
+        };
+        self.play_tracks(tracks);
+    }
+
+    /// Adds the selected artist, album or track to the queue, at the front if `next`.
+    pub fn enqueue_library_selection(&mut self, next: bool) {
+        let tracks = self.library_selection_tracks();
+        self.enqueue_tracks(tracks, next);
+    }
+
+    fn enqueue_tracks(&mut self, tracks: Vec<usize>, next: bool) {
+        if tracks.is_empty() {
+            return;
+        }
+
+        let message = self.describe_tracks(&tracks);
+        self.checkpoint(if next { "play next" } else { "add to queue" });
+        if next {
+            self.queue.splice(0..0, tracks);
+        } else {
+            self.queue.extend(tracks);
+        }
+        self.marked_queue.clear();
+        self.clamp_queue_selection();
+        self.set_status(format!(
+            "{}: {}",
+            if next { "Playing next" } else { "Queued" },
+            message
+        ));
+    }
+
+    /// Rebuilds the folder tree, keeping expanded folders and the selected row.
+    fn rebuild_folders(&mut self) {
+        let selected = self.folder_selected().map(|row| match row {
+            FolderRow::Folder { path, .. } => path,
+            FolderRow::Track { index, .. } => self.tracks[index].path.clone(),
+        });
+        let expanded = std::mem::take(&mut self.folder_tree.expanded);
+
+        self.folder_tree = FolderTree::build(&self.config.music_dir, &self.tracks);
+        self.folder_tree.expanded = expanded;
+
+        let rows = self.folder_tree.rows();
+        let row = selected
+            .and_then(|path| {
+                rows.iter().position(|row| match row {
+                    FolderRow::Folder { path: p, .. } => *p == path,
+                    FolderRow::Track { index, .. } => self.tracks[*index].path == path,
+                })
+            })
+            .or(if rows.is_empty() { None } else { Some(0) });
+        self.folder_state.select(row);
+    }
+
+    pub fn folder_selected(&self) -> Option<FolderRow> {
+        let pos = self.folder_state.selected()?;
+        self.folder_tree.rows().into_iter().nth(pos)
+    }
+
+    /// Folder under the selection: the folder itself or the one holding the file.
+    fn folder_selected_dir(&self) -> Option<PathBuf> {
+        match self.folder_selected()? {
+            FolderRow::Folder { path, .. } => Some(path),
+            FolderRow::Track { index, .. } => self.tracks[index].path.parent().map(PathBuf::from),
+        }
+    }
+
+    /// Expands or collapses the selected folder. Collapsing from inside a
+    /// folder moves the selection to that folder.
+    pub fn expand_folder(&mut self, expand: bool) {
+        let Some(row) = self.folder_selected() else {
+            return;
+        };
+
+        match row {
+            FolderRow::Folder { path, .. } if expand => {
+                self.folder_tree.expanded.insert(path);
+            }
+            FolderRow::Folder { path, .. } if self.folder_tree.expanded.contains(&path) => {
+                self.folder_tree.expanded.remove(&path);
+            }
+            _ if !expand => self.select_parent_folder(),
+            _ => {}
+        }
+    }
+
+    fn select_parent_folder(&mut self) {
+        let Some(pos) = self.folder_state.selected() else {
+            return;
+        };
+        let rows = self.folder_tree.rows();
+        let depth = match rows.get(pos) {
+            Some(FolderRow::Folder { depth, .. } | FolderRow::Track { depth, .. }) => *depth,
+            None => return,
+        };
+        if depth == 0 {
+            return;
+        }
+
+        let parent = rows[..pos]
+            .iter()
+            .rposition(|row| matches!(row, FolderRow::Folder { depth: d, .. } if *d == depth - 1));
+        if let Some(parent) = parent {
+            self.folder_state.select(Some(parent));
+        }
+    }
+
+    /// Toggles the selected folder, or plays its folder from the selected file.
+    pub fn activate_folder_row(&mut self) {
+        match self.folder_selected() {
+            Some(FolderRow::Folder { path, .. }) => {
+                let expand = !self.folder_tree.expanded.contains(&path);
+                self.expand_folder(expand);
+            }
+            Some(FolderRow::Track { index, .. }) => {
+                let Some(dir) = self.folder_selected_dir() else {
+                    return;
+                };
+                let tracks = self
+                    .folder_tree
+                    .folder(&dir)
+                    .map(|folder| folder.tracks.clone())
+                    .unwrap_or_default();
+                let start = tracks.iter().position(|&i| i == index).unwrap_or(0);
+                self.play_tracks(tracks[start..].to_vec());
+            }
+            None => {}
+        }
+    }
+
+    /// Plays everything under the selected folder, recursively.
+    pub fn play_folder(&mut self) {
+        let tracks = self
+            .folder_selected_dir()
+            .and_then(|dir| self.folder_tree.folder(&dir))
+            .map(|folder| folder.all_tracks())
+            .unwrap_or_default();
+        if tracks.is_empty() {
+            self.set_status("No tracks in folder".to_string());
+            return;
+        }
+        self.play_tracks(tracks);
+    }
+
+    /// Queues the selected file, or everything under the selected folder.
+    pub fn enqueue_folder(&mut self, next: bool) {
+        let tracks = match self.folder_selected() {
+            Some(FolderRow::Folder { path, .. }) => self
+                .folder_tree
+                .folder(&path)
+                .map(|folder| folder.all_tracks())
+                .unwrap_or_default(),
+            Some(FolderRow::Track { index, .. }) => vec![index],
+            None => Vec::new(),
+        };
+        self.enqueue_tracks(tracks, next);
+    }
+
+    fn rebuild_track_lookup(&mut self) {
+        self.track_lookup = self
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.path.clone(), i))
+            .collect();
+    }
+
+    pub fn track_index(&self, path: &PathBuf) -> Option<usize> {
+        self.track_lookup.get(path).copied()
+    }
+
+    /// Copies playlist files found in the music directory into the playlists
+    /// directory, once per source file.
+    pub fn import_playlists(&mut self, files: &[PathBuf]) {
+        let mut imported = 0;
+        let mut unresolved = Vec::new();
+
+        for file in files {
+            if self.imported_playlists.contains(file) {
+                continue;
+            }
+            if let Ok((playlist, missing)) = Playlist::load(file, &self.tracks)
+                && !self.playlist_name_taken(&playlist.name)
+            {
+                self.add_playlist(playlist);
+                imported += 1;
+                unresolved.extend(missing);
+            }
+            self.imported_playlists.push(file.clone());
+        }
+
+        if imported > 0 {
+            self.set_status(format!(
+                "Imported {} playlist(s){}",
+                imported,
+                unresolved_report(&unresolved)
+            ));
+        }
+    }
+
+    fn playlist_name_taken(&self, name: &str) -> bool {
+        self.playlists
+            .iter()
+            .any(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    fn add_playlist(&mut self, playlist: Playlist) -> usize {
+        let pos = self
+            .playlists
+            .iter()
+            .position(|p| p.name.to_lowercase() > playlist.name.to_lowercase())
+            .unwrap_or(self.playlists.len());
+        self.playlists.insert(pos, playlist);
+        self.save_playlist(pos);
+        pos
+    }
+
+    fn save_playlist(&mut self, index: usize) {
+        if let Err(e) = self.playlists[index].save(&self.tracks, self.config.playlist_paths) {
+            self.set_status(format!("Failed to save playlist: {}", e));
+        }
+    }
+
+    pub fn selected_playlist(&self) -> Option<usize> {
+        self.playlist_state
+            .selected()
+            .filter(|&i| i < self.playlists.len())
+    }
+
+    pub fn playlist_entry_selected(&self) -> Option<usize> {
+        let playlist = &self.playlists[self.open_playlist?];
+        self.playlist_entry_state
+            .selected()
+            .filter(|&i| i < playlist.entries.len())
+    }
+
+    /// Re-evaluates smart playlists after ratings or play counts change,
+    /// keeping the selection in an open one within its entries.
+    fn refresh_smart_playlists(&mut self) {
+        for playlist in &mut self.playlists {
+            playlist.refresh(&self.tracks, &self.collator);
+        }
+        if let Some(index) = self.open_playlist
+            && self.playlists[index].smart.is_some()
+        {
+            let len = self.playlists[index].entries.len();
+            let selected = self.playlist_entry_state.selected();
+            self.playlist_entry_state.select(
+                selected
+                    .map(|i| i.min(len.saturating_sub(1)))
+                    .filter(|_| len > 0),
+            );
+        }
+    }
+
+    fn reject_smart_edit(&mut self, index: usize) -> bool {
+        if self.playlists[index].smart.is_some() {
+            self.set_status(format!(
+                "{} is a smart playlist; edit its rules in {}",
+                self.playlists[index].name,
+                self.playlists[index]
+                    .file_path()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default()
+            ));
+            return true;
+        }
+        false
+    }
+
+    pub fn open_selected_playlist(&mut self) {
+        if let Some(index) = self.selected_playlist() {
+            self.playlists[index].refresh(&self.tracks, &self.collator);
+            self.open_playlist = Some(index);
+            let has_entries = !self.playlists[index].entries.is_empty();
+            self.playlist_entry_state
+                .select(if has_entries { Some(0) } else { None });
+        }
+    }
+
+    pub fn close_playlist(&mut self) {
+        self.open_playlist = None;
+    }
+
+    /// Plays a playlist from `start`, replacing the queue with the entries after it.
+    pub fn play_playlist(&mut self, index: usize, start: usize) {
+        if self.open_playlist != Some(index) {
+            self.playlists[index].refresh(&self.tracks, &self.collator);
+        }
+        let tracks: Vec<usize> = self.playlists[index].entries[start..]
+            .iter()
+            .filter_map(|path| self.track_index(path))
+            .collect();
+
+        if tracks.is_empty() {
+            self.set_status("No playable tracks in playlist".to_string());
+            return;
+        }
+        self.play_tracks(tracks);
+    }
+
+    /// Plays the first of `tracks` and replaces the queue with the rest.
+    fn play_tracks(&mut self, mut tracks: Vec<usize>) {
+        if tracks.is_empty() {
+            return;
+        }
+        if !self.queue.is_empty() {
+            self.checkpoint("replace queue");
+        }
+        let first = tracks.remove(0);
+        self.queue = tracks;
+        self.marked_queue.clear();
+        self.clamp_queue_selection();
+        self.select_track(first);
+        self.play_track(first);
+    }
+
+    pub fn play_selected_playlist(&mut self) {
+        match self.open_playlist {
+            Some(index) => {
+                let start = self.playlist_entry_selected().unwrap_or(0);
+                self.play_playlist(index, start);
+            }
+            None => {
+                if let Some(index) = self.selected_playlist() {
+                    self.play_playlist(index, 0);
+                }
+            }
+        }
+    }
+
+    pub fn add_selected_to_playlist(&mut self) {
+        let tracks = self.batch_tracks();
+        if tracks.is_empty() {
+            return;
+        }
+        let Some(index) = self.selected_playlist() else {
+            self.set_status("No playlist selected. Create one in the playlists view.".to_string());
+            return;
+        };
+        if self.reject_smart_edit(index) {
+            return;
+        }
+
+        let message = format!(
+            "Added to {}: {}",
+            self.playlists[index].name,
+            self.describe_tracks(&tracks)
+        );
+        let name = self.playlists[index].name.clone();
+        self.checkpoint_playlists("add to playlist", &[name]);
+        for track in tracks {
+            let path = self.tracks[track].path.clone();
+            self.playlists[index].entries.push(path);
+        }
+        self.save_playlist(index);
+        self.clear_marks();
+        self.set_status(message);
+    }
+
+    pub fn move_playlist_entry(&mut self, up: bool) {
+        let (Some(index), Some(pos)) = (self.open_playlist, self.playlist_entry_selected()) else {
+            return;
+        };
+        if self.reject_smart_edit(index) {
+            return;
+        }
+        let len = self.playlists[index].entries.len();
+        let target = if up {
+            pos.checked_sub(1)
+        } else {
+            Some(pos + 1).filter(|&p| p < len)
+        };
+        if let Some(target) = target {
+            let name = self.playlists[index].name.clone();
+            self.checkpoint_playlists("move in playlist", &[name]);
+            self.playlists[index].entries.swap(pos, target);
+            self.playlist_entry_state.select(Some(target));
+            self.save_playlist(index);
+        }
+    }
+
+    pub fn remove_playlist_entry(&mut self) {
+        let (Some(index), Some(pos)) = (self.open_playlist, self.playlist_entry_selected()) else {
+            return;
+        };
+        if self.reject_smart_edit(index) {
+            return;
+        }
+        let name = self.playlists[index].name.clone();
+        self.checkpoint_playlists("remove from playlist", &[name]);
+        self.playlists[index].entries.remove(pos);
+        let len = self.playlists[index].entries.len();
+        self.playlist_entry_state.select(if len == 0 {
+            None
+        } else {
+            Some(pos.min(len - 1))
+        });
+        self.save_playlist(index);
+    }
+
+    pub fn delete_selected_playlist(&mut self) {
+        if self.open_playlist.is_some() {
+            return;
+        }
+        if let Some(index) = self.selected_playlist() {
+            let name = self.playlists[index].name.clone();
+            self.checkpoint_playlists("delete playlist", &[name]);
+            let playlist = self.playlists.remove(index);
+            if let Err(e) = playlist.delete() {
+                self.set_status(format!("Failed to delete playlist: {}", e));
+            } else {
+                self.set_status(format!("Deleted playlist {}", playlist.name));
+            }
+            let len = self.playlists.len();
+            self.playlist_state.select(if len == 0 {
+                None
+            } else {
+                Some(index.min(len - 1))
+            });
+        }
+    }
+
+    pub fn start_prompt(&mut self, kind: PromptKind) {
+        let needs_playlist = matches!(kind, PromptKind::Rename | PromptKind::Export);
+        if needs_playlist && self.selected_playlist().is_none() {
+            return;
+        }
+
+        let text = match kind {
+            PromptKind::Rename => self
+                .selected_playlist()
+                .map(|i| self.playlists[i].name.clone())
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+        self.prompt = Some(Prompt { kind, text });
+    }
+
+    pub fn prompt_input(&mut self, c: char) {
+        if let Some(prompt) = &mut self.prompt {
+            prompt.text.push(c);
+        }
+        self.update_search();
+    }
+
+    pub fn prompt_backspace(&mut self) {
+        if let Some(prompt) = &mut self.prompt {
+            prompt.text.pop();
+        }
+        self.update_search();
+    }
+
+    pub fn cancel_prompt(&mut self) {
+        if let Some(prompt) = self.prompt.take()
+            && prompt.kind == PromptKind::Search
+        {
+            self.clear_filter();
+        }
+    }
+
+    pub fn submit_prompt(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        let text = prompt.text.trim().to_string();
+        if prompt.kind == PromptKind::Search {
+            if text.is_empty() {
+                self.clear_filter();
+            } else if self.filter.as_ref().is_some_and(|f| f.matches.is_empty()) {
+                self.set_status(format!("No matches for {}", text));
+            }
+            return;
+        }
+        if text.is_empty() {
+            return;
+        }
+
+        match prompt.kind {
+            PromptKind::NewPlaylist => {
+                if self.playlist_name_taken(&text) {
+                    self.set_status(format!("Playlist {} already exists", text));
+                    return;
+                }
+                self.checkpoint_playlists("new playlist", std::slice::from_ref(&text));
+                let index = self.add_playlist(Playlist::new(&text));
+                self.playlist_state.select(Some(index));
+            }
+            PromptKind::NewSmart => match SmartRules::parse(&text) {
+                Ok((name, _)) if self.playlist_name_taken(&name) => {
+                    self.set_status(format!("Playlist {} already exists", name));
+                }
+                Ok((name, rules)) => {
+                    let playlist = Playlist::new_smart(&name, rules, &self.tracks, &self.collator);
+                    let message = format!("{} matches {} track(s)", name, playlist.entries.len());
+                    self.checkpoint_playlists("new smart playlist", &[name]);
+                    let index = self.add_playlist(playlist);
+                    self.playlist_state.select(Some(index));
+                    self.set_status(message);
+                }
+                Err(e) => self.set_status(format!("Invalid smart playlist: {}", e)),
+            },
+            PromptKind::Rename => {
+                let Some(index) = self.selected_playlist() else {
+                    return;
+                };
+                if self.playlist_name_taken(&text)
+                    && !self.playlists[index].name.eq_ignore_ascii_case(&text)
+                {
+                    self.set_status(format!("Playlist {} already exists", text));
+                    return;
+                }
+                let names = [self.playlists[index].name.clone(), text.clone()];
+                self.checkpoint_playlists("rename playlist", &names);
+                let mut playlist = self.playlists.remove(index);
+                playlist.delete().ok();
+                playlist.name = text;
+                let index = self.add_playlist(playlist);
+                self.playlist_state.select(Some(index));
+            }
+            PromptKind::Import => match Playlist::load(&expand_home(&text), &self.tracks) {
+                Ok((playlist, _)) if self.playlist_name_taken(&playlist.name) => {
+                    self.set_status(format!("Playlist {} already exists", playlist.name));
+                }
+                Ok((playlist, unresolved)) => {
+                    let message = format!(
+                        "Imported {} ({} entries){}",
+                        playlist.name,
+                        playlist.entries.len(),
+                        unresolved_report(&unresolved)
+                    );
+                    self.checkpoint_playlists(
+                        "import playlist",
+                        std::slice::from_ref(&playlist.name),
+                    );
+                    let index = self.add_playlist(playlist);
+                    self.playlist_state.select(Some(index));
+                    self.set_status(message);
+                }
+                Err(e) => self.set_status(format!("Failed to import playlist: {}", e)),
+            },
+            PromptKind::Export => {
+                let Some(index) = self.selected_playlist() else {
+                    return;
+                };
+                let target = expand_home(&text);
+                match self.playlists[index].export(
+                    &target,
+                    &self.tracks,
+                    self.config.playlist_paths,
+                ) {
+                    Ok(()) => self.set_status(format!("Exported to {}", target.display())),
+                    Err(e) => self.set_status(format!("Failed to export playlist: {}", e)),
+                }
+            }
+            PromptKind::Search => {}
+            PromptKind::StopAfter => match text.parse::<u32>() {
+                Ok(0) => {
+                    self.stop_after = None;
+                    self.set_status("Stop after cancelled".to_string());
+                }
+                Ok(count) => {
+                    self.stop_after = Some(count);
+                    self.set_status(format!("Stopping after {} track(s)", count));
+                }
+                Err(_) => self.set_status(format!("Not a track count: {}", text)),
+            },
+        }
+    }
+
+    /// Opens the search prompt over the playlist view, starting from the current query.
+    pub fn start_search(&mut self) {
+        if self.view != View::Playlist {
+            self.visual_anchor = None;
+            self.marked_queue.clear();
+        }
+        self.view = View::Playlist;
+        self.show_lyrics = false;
+        let text = self
+            .filter
+            .as_ref()
+            .map(|f| f.query.clone())
+            .unwrap_or_default();
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Search,
+            text,
+        });
+    }
+
+    /// Re-filters the playlist view as the search prompt is edited.
+    fn update_search(&mut self) {
+        let Some(prompt) = &self.prompt else {
+            return;
+        };
+        if prompt.kind != PromptKind::Search {
+            return;
+        }
+
+        let query = prompt.text.trim().to_string();
+        let selected = self.selected();
+        if query.is_empty() {
+            self.filter = None;
+        } else {
+            self.filter = Some(TrackFilter::new(&query, &self.tracks));
+        }
+
+        let matches = self.visible_tracks();
+        let row = selected
+            .and_then(|index| matches.iter().position(|&i| i == index))
+            .or(if matches.is_empty() { None } else { Some(0) });
+        self.list_state.select(row);
+    }
+
+    /// Drops the search filter, keeping the selected track selected.
+    pub fn clear_filter(&mut self) {
+        let selected = self.selected();
+        self.filter = None;
+        match selected {
+            Some(index) => self.select_track(index),
+            None if !self.tracks.is_empty() => self.list_state.select(Some(0)),
+            None => {}
+        }
+    }
+
+    /// Jumps to the next or previous search match, wrapping around.
+    pub fn search_next(&mut self, forward: bool) {
+        let Some(filter) = &self.filter else {
+            return;
+        };
+        let len = filter.matches.len();
+        if len == 0 {
+            return;
+        }
+
+        let row = match self.list_state.selected() {
+            Some(row) if forward => (row + 1) % len,
+            Some(row) => (row + len - 1) % len,
+            None => 0,
+        };
+        self.list_state.select(Some(row));
+    }
+
+    pub fn set_status(&mut self, message: String) {
+        self.status_message = Some((message, std::time::Instant::now()));
+    }
+
+    pub fn check_status_message(&mut self) {
+        if let Some(message) = self.submitter.as_ref().and_then(|s| s.poll_message()) {
+            self.set_status(message);
+        }
+        if let Some((_, time)) = &self.status_message {
+            if time.elapsed().as_secs() > 3 {
+                self.status_message = None;
+            }
+        }
+    }
+}
+
+/// Maps a saved play order back onto library indices. Tracks that disappeared
+/// are dropped and tracks missing from the saved order are shuffled in at the end.
+fn restore_play_order(
+    saved: &[PathBuf],
+    saved_index: Option<usize>,
+    positions: &HashMap<&PathBuf, usize>,
+) -> (Vec<usize>, Option<usize>) {
+    let mut order = Vec::with_capacity(positions.len());
+    let mut order_index = None;
+
+    for (i, path) in saved.iter().enumerate() {
+        if let Some(&index) = positions.get(path) {
+            order.push(index);
+        }
+        if Some(i) == saved_index {
+            order_index = order.len().checked_sub(1);
+        }
+    }
+
+    let seen: HashSet<usize> = order.iter().copied().collect();
+    let mut added: Vec<usize> = positions
+        .values()
+        .copied()
+        .filter(|i| !seen.contains(i))
+        .collect();
+    added.sort_unstable();
+    added.shuffle(&mut rand::thread_rng());
+    order.extend(added);
+
+    (order, order_index)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Summarises playlist entries that could not be matched to the library.
+fn unresolved_report(unresolved: &[String]) -> String {
+    if unresolved.is_empty() {
+        return String::new();
+    }
+
+    let shown: Vec<&str> = unresolved.iter().take(3).map(|s| s.as_str()).collect();
+    let more = if unresolved.len() > shown.len() {
+        format!(", +{} more", unresolved.len() - shown.len())
+    } else {
+        String::new()
+    };
+    format!(
+        "\n{} unresolved: {}{}",
+        unresolved.len(),
+        shown.join(", "),
+        more
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::columns::{SortKey, SortSpec};
+
+    fn track(path: &str, title: &str) -> Track {
+        let mut track = Track::from_path(PathBuf::from(path));
+        track.title = title.to_string();
+        track.duration = 200;
+        track
+    }
+
+    #[test]
+    fn sorting_mid_session_credits_the_playing_track() {
+        let mut tracks = [
+            track("/music/a.mp3", "Zulu"),
+            track("/music/b.mp3", "Alpha"),
+            track("/music/c.mp3", "Mike"),
+        ];
+        let mut session = PlaySession::new(&tracks[0]);
+        session.listened = std::time::Duration::from_secs(150);
+
+        let sort = SortSpec(vec![SortKey {
+            column: Column::Title,
+            descending: false,
+        }]);
+        let collator = Collator::new(&Config::default());
+        tracks.sort_by(|a, b| sort.compare(a, b, &collator));
+
+        let (index, record) = session
+            .finish(&tracks, false)
+            .expect("playing track is still in the library");
+        assert_eq!(index, 2);
+        assert_eq!(tracks[index].path, PathBuf::from("/music/a.mp3"));
+        assert_eq!(record.path, tracks[index].path);
+        assert_eq!(record.title, "Zulu");
+        assert!(!record.skipped);
+    }
+
+    #[test]
+    fn finishing_needs_a_second_of_listening_to_a_known_track() {
+        let tracks = [track("/music/a.mp3", "Zulu")];
+        let mut session = PlaySession::new(&tracks[0]);
+        assert!(session.finish(&tracks, true).is_none());
+
+        session.listened = std::time::Duration::from_secs(20);
+        let (_, record) = session.finish(&tracks, false).unwrap();
+        assert!(record.skipped);
+        assert!(
+            session
+                .finish(&[track("/music/b.mp3", "Alpha")], true)
+                .is_none()
+        );
+    }
 }
@@ -0,0 +1,196 @@
+use std::cmp::Ordering;
+use std::path::Path;
+
+use icu_collator::{CollatorOptions, Numeric};
+use icu_locid::Locale;
+
+use crate::config::Config;
+
+/// Orders names the way a reader expects: by the rules of the user's
+/// locale, numbers by value ("Track 2" before "Track 10") and leading
+/// articles ignored ("The Beatles" among the B's).
+pub struct Collator {
+    collator: icu_collator::Collator,
+    /// Lowercase articles, each with the space that must follow it unless
+    /// it ends in an apostrophe, as in "L'".
+    articles: Vec<String>,
+}
+
+impl Collator {
+    pub fn new(config: &Config) -> Self {
+        let locale = config
+            .sort_locale
+            .clone()
+            .or_else(system_locale)
+            .and_then(|name| name.parse::<Locale>().ok())
+            .unwrap_or(Locale::UND);
+
+        let mut options = CollatorOptions::new();
+        options.numeric = Some(Numeric::On);
+        let collator = icu_collator::Collator::try_new(&(&locale).into(), options)
+            .or_else(|_| icu_collator::Collator::try_new(&Default::default(), options))
+            .expect("Root collation data is compiled in");
+
+        let articles = config
+            .sort_articles
+            .iter()
+            .map(|article| article.trim())
+            .filter(|article| !article.is_empty())
+            .map(|article| {
+                let article = article.to_lowercase();
+                if article.ends_with('\'') {
+                    article
+                } else {
+                    article + " "
+                }
+            })
+            .collect();
+
+        Self { collator, articles }
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        self.collator.compare(a, b)
+    }
+
+    /// What a name sorts as: its sort tag when there is one, which is used
+    /// as written, otherwise the name with any leading article left out.
+    pub fn sort_name<'a>(&self, name: &'a str, sort_tag: Option<&'a str>) -> &'a str {
+        sort_tag.unwrap_or_else(|| self.strip_article(name))
+    }
+
+    /// Compares paths directory by directory, so siblings stay together.
+    pub fn compare_paths(&self, a: &Path, b: &Path) -> Ordering {
+        let mut a = a.components();
+        let mut b = b.components();
+        loop {
+            match (a.next(), b.next()) {
+                (Some(x), Some(y)) => {
+                    let order = self.collator.compare(
+                        &x.as_os_str().to_string_lossy(),
+                        &y.as_os_str().to_string_lossy(),
+                    );
+                    if order.is_ne() {
+                        return order;
+                    }
+                }
+                (Some(_), None) => return Ordering::Greater,
+                (None, Some(_)) => return Ordering::Less,
+                (None, None) => return Ordering::Equal,
+            }
+        }
+    }
+
+    fn strip_article<'a>(&self, name: &'a str) -> &'a str {
+        let name = name.trim_start();
+        for article in &self.articles {
+            if let Some(prefix) = name.get(..article.len())
+                && prefix.to_lowercase() == *article
+                && name.len() > article.len()
+            {
+                return name[article.len()..].trim_start();
+            }
+        }
+        name
+    }
+}
+
+/// The collation locale from the environment, e.g. `de-DE` for `LANG=de_DE.UTF-8`.
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_COLLATE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            let name = value.split(['.', '@']).next().unwrap_or_default();
+            name.replace('_', "-")
+        })
+        .filter(|name| name != "C" && name != "POSIX")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collator(locale: Option<&str>, articles: &[&str]) -> Collator {
+        Collator::new(&Config {
+            sort_locale: locale.map(str::to_string),
+            sort_articles: articles.iter().map(|a| a.to_string()).collect(),
+            ..Config::default()
+        })
+    }
+
+    fn sorted(collator: &Collator, names: &[&str]) -> Vec<String> {
+        let mut names: Vec<&str> = names.to_vec();
+        names.sort_by(|a, b| {
+            collator.compare(collator.sort_name(a, None), collator.sort_name(b, None))
+        });
+        names.into_iter().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn orders_numbers_by_value() {
+        let collator = collator(Some("en"), &[]);
+        assert_eq!(
+            sorted(&collator, &["Track 10", "track 2", "Track 1", "Track 02b"]),
+            ["Track 1", "track 2", "Track 02b", "Track 10"]
+        );
+        assert_eq!(
+            collator.compare_paths(Path::new("/m/Disc 10/a.mp3"), Path::new("/m/Disc 9/b.mp3")),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn ignores_leading_articles() {
+        let collator = collator(Some("en"), &["The", "a", "L'"]);
+        assert_eq!(
+            sorted(
+                &collator,
+                &["Cream", "The Beatles", "Abba", "L'Amour", "the the"]
+            ),
+            ["Abba", "L'Amour", "The Beatles", "Cream", "the the"]
+        );
+        // A name that is only an article, or merely starts with its letters,
+        // is left alone.
+        assert_eq!(collator.strip_article("The"), "The");
+        assert_eq!(collator.strip_article("Theory"), "Theory");
+        assert_eq!(collator.strip_article("A Tribe"), "Tribe");
+    }
+
+    #[test]
+    fn sort_tags_take_precedence() {
+        let collator = collator(Some("en"), &["The"]);
+        assert_eq!(collator.sort_name("The Beatles", Some("Zeta")), "Zeta");
+        assert_eq!(collator.sort_name("The Beatles", None), "Beatles");
+        assert_eq!(
+            collator.compare(
+                collator.sort_name("Miles Davis", Some("Davis, Miles")),
+                collator.sort_name("Cream", None),
+            ),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn falls_back_to_root_collation() {
+        for locale in [Some("not a locale!"), Some("zz-ZZ"), Some("")] {
+            let collator = collator(locale, &[]);
+            assert_eq!(collator.compare("Track 2", "Track 10"), Ordering::Less);
+            assert_eq!(collator.compare("émile", "Eric"), Ordering::Less);
+        }
+    }
+
+    #[test]
+    fn follows_the_configured_locale() {
+        // Swedish sorts Å after Z; the root order puts it with A.
+        assert_eq!(
+            collator(Some("sv-SE"), &[]).compare("Åsa", "Zorn"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            collator(Some("en-US"), &[]).compare("Åsa", "Zorn"),
+            Ordering::Less
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::collate::Collator;
+use crate::scanner::Track;
+
+/// Narrowest a column can be resized to.
+pub const MIN_WIDTH: u16 = 3;
+
+/// A column of the track table, also usable as a sort key.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    File,
+    Number,
+    Title,
+    Artist,
+    Album,
+    Year,
+    Genre,
+    Duration,
+    Bitrate,
+    Rating,
+    Plays,
+}
+
+impl Column {
+    pub const ALL: [Column; 11] = [
+        Column::File,
+        Column::Number,
+        Column::Title,
+        Column::Artist,
+        Column::Album,
+        Column::Year,
+        Column::Genre,
+        Column::Duration,
+        Column::Bitrate,
+        Column::Rating,
+        Column::Plays,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Column::File => "File",
+            Column::Number => "#",
+            Column::Title => "Title",
+            Column::Artist => "Artist",
+            Column::Album => "Album",
+            Column::Year => "Year",
+            Column::Genre => "Genre",
+            Column::Duration => "Time",
+            Column::Bitrate => "kbps",
+            Column::Rating => "Rating",
+            Column::Plays => "Plays",
+        }
+    }
+
+    /// Key that picks this column after `o` or `O`.
+    pub fn key(&self) -> char {
+        match self {
+            Column::File => 'f',
+            Column::Number => 'n',
+            Column::Title => 't',
+            Column::Artist => 'a',
+            Column::Album => 'l',
+            Column::Year => 'y',
+            Column::Genre => 'g',
+            Column::Duration => 'd',
+            Column::Bitrate => 'b',
+            Column::Rating => 'r',
+            Column::Plays => 'p',
+        }
+    }
+
+    pub fn from_key(key: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.key() == key)
+    }
+
+    pub fn default_width(&self) -> u16 {
+        match self {
+            Column::File => 30,
+            Column::Number => 4,
+            Column::Title => 32,
+            Column::Artist => 22,
+            Column::Album => 22,
+            Column::Year => 5,
+            Column::Genre => 12,
+            Column::Duration => 6,
+            Column::Bitrate => 5,
+            Column::Rating => 8,
+            Column::Plays => 5,
+        }
+    }
+
+    /// Numbers line up better against the right edge.
+    pub fn right_aligned(&self) -> bool {
+        matches!(
+            self,
+            Column::Number | Column::Year | Column::Duration | Column::Bitrate | Column::Plays
+        )
+    }
+
+    /// Columns where more is better start out sorted high to low.
+    fn descending_first(&self) -> bool {
+        matches!(self, Column::Rating | Column::Plays)
+    }
+
+    /// Cell text; the rating column is drawn as stars instead.
+    pub fn text(&self, track: &Track) -> String {
+        let number = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+        match self {
+            Column::File => track
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            Column::Number => number(track.track_number),
+            Column::Title => track.title.clone(),
+            Column::Artist => track.artist.clone(),
+            Column::Album => track.album.clone().unwrap_or_default(),
+            Column::Year => number(track.year),
+            Column::Genre => track.genre.clone().unwrap_or_default(),
+            Column::Duration => format!("{}:{:02}", track.duration / 60, track.duration % 60),
+            Column::Bitrate => number(track.bitrate),
+            Column::Rating => String::new(),
+            Column::Plays => track.plays.to_string(),
+        }
+    }
+
+    /// Whether the track has no value to sort by in this column.
+    fn missing(&self, track: &Track) -> bool {
+        match self {
+            Column::Number => track.track_number.is_none(),
+            Column::Album => track.album.is_none(),
+            Column::Year => track.year.is_none(),
+            Column::Genre => track.genre.is_none(),
+            Column::Bitrate => track.bitrate.is_none(),
+            _ => false,
+        }
+    }
+
+    /// Ascending order; missing values sort after present ones.
+    fn compare(&self, a: &Track, b: &Track, collator: &Collator) -> Ordering {
+        let names = |a: &str, a_tag: &Option<String>, b: &str, b_tag: &Option<String>| {
+            collator.compare(
+                collator.sort_name(a, a_tag.as_deref()),
+                collator.sort_name(b, b_tag.as_deref()),
+            )
+        };
+        match self {
+            Column::File => collator.compare_paths(&a.path, &b.path),
+            Column::Number => {
+                compare_option(a.track_number.as_ref(), b.track_number.as_ref(), Ord::cmp)
+            }
+            Column::Title => names(&a.title, &a.title_sort, &b.title, &b.title_sort),
+            Column::Artist => names(&a.artist, &a.artist_sort, &b.artist, &b.artist_sort),
+            Column::Album => compare_option(a.album.as_deref(), b.album.as_deref(), |x, y| {
+                names(x, &a.album_sort, y, &b.album_sort)
+            }),
+            Column::Year => compare_option(a.year.as_ref(), b.year.as_ref(), Ord::cmp),
+            Column::Genre => compare_option(a.genre.as_deref(), b.genre.as_deref(), |x, y| {
+                collator.compare(x, y)
+            }),
+            Column::Duration => a.duration.cmp(&b.duration),
+            Column::Bitrate => compare_option(a.bitrate.as_ref(), b.bitrate.as_ref(), Ord::cmp),
+            Column::Rating => (a.favorite, a.rating).cmp(&(b.favorite, b.rating)),
+            Column::Plays => a.plays.cmp(&b.plays),
+        }
+    }
+}
+
+fn compare_option<T>(
+    a: Option<T>,
+    b: Option<T>,
+    compare: impl FnOnce(T, T) -> Ordering,
+) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => compare(a, b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortKey {
+    pub column: Column,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+/// Sort keys in priority order; ties fall back to the file path.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SortSpec(pub Vec<SortKey>);
+
+impl Default for SortSpec {
+    fn default() -> Self {
+        Self(vec![SortKey {
+            column: Column::File,
+            descending: false,
+        }])
+    }
+}
+
+impl SortSpec {
+    /// Missing values stay last whichever way a key runs.
+    pub fn compare(&self, a: &Track, b: &Track, collator: &Collator) -> Ordering {
+        self.0
+            .iter()
+            .map(|key| {
+                let order = key.column.compare(a, b, collator);
+                if key.descending && !key.column.missing(a) && !key.column.missing(b) {
+                    order.reverse()
+                } else {
+                    order
+                }
+            })
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.path.cmp(&b.path))
+    }
+
+    /// Sorts by `column` alone, or flips its direction if it already leads.
+    pub fn sort_by(&mut self, column: Column) {
+        let descending = match self.0.first() {
+            Some(key) if key.column == column => !key.descending,
+            _ => column.descending_first(),
+        };
+        self.0 = vec![SortKey { column, descending }];
+    }
+
+    /// Adds `column` as the last sort key, or flips it if already used.
+    pub fn then_by(&mut self, column: Column) {
+        match self.0.iter_mut().find(|key| key.column == column) {
+            Some(key) => key.descending = !key.descending,
+            None => self.0.push(SortKey {
+                column,
+                descending: column.descending_first(),
+            }),
+        }
+    }
+
+    pub fn direction(&self, column: Column) -> Option<bool> {
+        self.0
+            .iter()
+            .find(|key| key.column == column)
+            .map(|key| key.descending)
+    }
+
+    /// Short form such as `Artist↑ Year↓` for the status bar.
+    pub fn describe(&self) -> String {
+        self.0
+            .iter()
+            .map(|key| {
+                let arrow = if key.descending { '↓' } else { '↑' };
+                let label = match key.column {
+                    Column::Number => "Track",
+                    column => column.label(),
+                };
+                format!("{}{}", label, arrow)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::config::Config;
+
+    fn sorted(spec: &SortSpec, years: &[Option<u32>]) -> Vec<Option<u32>> {
+        let mut tracks: Vec<Track> = years
+            .iter()
+            .enumerate()
+            .map(|(i, &year)| {
+                let mut track = Track::from_path(PathBuf::from(format!("/m/{}.mp3", i)));
+                track.year = year;
+                track
+            })
+            .collect();
+        let collator = Collator::new(&Config::default());
+        tracks.sort_by(|a, b| spec.compare(a, b, &collator));
+        tracks.iter().map(|t| t.year).collect()
+    }
+
+    #[test]
+    fn missing_values_sort_last_in_both_directions() {
+        let years = [None, Some(1999), Some(1971), None, Some(2004)];
+        let mut spec = SortSpec::default();
+        spec.sort_by(Column::Year);
+        assert_eq!(
+            sorted(&spec, &years),
+            [Some(1971), Some(1999), Some(2004), None, None]
+        );
+        spec.sort_by(Column::Year);
+        assert_eq!(spec.direction(Column::Year), Some(true));
+        assert_eq!(
+            sorted(&spec, &years),
+            [Some(2004), Some(1999), Some(1971), None, None]
+        );
+    }
+}
@@ -1,25 +1,134 @@
+use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
 
+use serde::{Deserialize, Serialize};
+
+use crate::columns::Column;
+
 pub const SUPPORTED_EXTENSIONS: &[&str] = &["mp3", "flac", "wav", "ogg"];
 
+/// Smallest volume step; anything below it would leave the volume keys stuck.
+pub const MIN_VOLUME_STEP: f32 = 0.01;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VolumeDisplay {
+    Percent,
+    Decibels,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlaylistPaths {
+    Absolute,
+    Relative,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScrobbleService {
+    #[default]
+    ListenBrainz,
+    LastFm,
+}
+
+/// Online scrobbling. `base_url` overrides the service's API root.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScrobbleConfig {
+    pub service: ScrobbleService,
+    pub base_url: Option<String>,
+    /// ListenBrainz user token.
+    pub token: String,
+    /// Last.fm API account and an already authorized session key.
+    pub api_key: String,
+    pub api_secret: String,
+    pub session_key: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub music_dir: PathBuf,
+    pub volume_step: f32,
+    pub max_volume_db: f32,
+    pub volume_display: VolumeDisplay,
+    pub silence_threshold_db: f32,
+    pub playlist_paths: PlaylistPaths,
+    /// Also store ratings in the files' tags (POPM, FMPS_RATING, RATING).
+    pub write_rating_tags: bool,
+    /// Where to write the `.scrobbler.log`; defaults to the tune data directory.
+    pub scrobbler_log: Option<PathBuf>,
+    pub scrobbling: Option<ScrobbleConfig>,
+    /// Recent plays that radio mode will not pick again.
+    pub radio_window: usize,
+    /// Columns of the track table, left to right.
+    pub columns: Vec<Column>,
+    /// Column widths in cells, overriding the built-in defaults.
+    pub column_widths: HashMap<Column, u16>,
+    /// Locale for sorting names, e.g. `de` or `sv-SE`; defaults to `LANG`.
+    pub sort_locale: Option<String>,
+    /// Leading words left out when sorting titles, artists and albums.
+    pub sort_articles: Vec<String>,
 }
 
 impl Config {
     pub fn new(music_dir_override: Option<PathBuf>) -> Self {
-        let music_dir = music_dir_override.unwrap_or_else(|| {
-            dirs::audio_dir()
-                .or_else(|| dirs::home_dir().map(|h| h.join("Music")))
-                .unwrap_or_else(|| PathBuf::from("."))
-        });
+        let mut config = Self::load();
+
+        if let Some(music_dir) = music_dir_override {
+            config.music_dir = music_dir;
+        }
+        config.volume_step = if config.volume_step.is_finite() {
+            config.volume_step.clamp(MIN_VOLUME_STEP, 1.0)
+        } else {
+            Self::default().volume_step
+        };
 
-        Self { music_dir }
+        config
+    }
+
+    fn load() -> Self {
+        if let Some(mut path) = dirs::config_dir() {
+            path.push("tune");
+            path.push("config.json");
+
+            if let Ok(content) = fs::read_to_string(&path)
+                && let Ok(config) = serde_json::from_str(&content)
+            {
+                return config;
+            }
+        }
+        Self::default()
     }
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self::new(None)
+        let music_dir = dirs::audio_dir()
+            .or_else(|| dirs::home_dir().map(|h| h.join("Music")))
+            .unwrap_or_else(|| PathBuf::from("."));
+
+        Self {
+            music_dir,
+            volume_step: 0.05,
+            max_volume_db: 0.0,
+            volume_display: VolumeDisplay::Percent,
+            silence_threshold_db: -60.0,
+            playlist_paths: PlaylistPaths::Absolute,
+            write_rating_tags: false,
+            scrobbler_log: None,
+            scrobbling: None,
+            radio_window: 50,
+            columns: vec![
+                Column::Number,
+                Column::Title,
+                Column::Artist,
+                Column::Album,
+                Column::Duration,
+                Column::Rating,
+            ],
+            column_widths: HashMap::new(),
+            sort_locale: None,
+            sort_articles: vec!["The".to_string(), "A".to_string(), "An".to_string()],
+        }
     }
 }
@@ -0,0 +1,438 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::{ChannelCount, Sample, SampleRate, Source};
+
+/// How often (in frames) the mixer picks up changed settings.
+const REFRESH_FRAMES: usize = 512;
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct ChannelSettings {
+    /// -1.0 is fully left, 1.0 is fully right.
+    pub balance: f32,
+    pub mono: bool,
+    pub swap: bool,
+}
+
+impl ChannelSettings {
+    fn apply(&self, left: Sample, right: Sample) -> (Sample, Sample) {
+        let (left, right) = if self.swap {
+            (right, left)
+        } else {
+            (left, right)
+        };
+
+        let (left, right) = if self.mono {
+            let mid = (left + right) / 2.0;
+            (mid, mid)
+        } else {
+            (left, right)
+        };
+
+        let left_gain = (1.0 - self.balance).min(1.0);
+        let right_gain = (1.0 + self.balance).min(1.0);
+
+        (left * left_gain, right * right_gain)
+    }
+}
+
+/// Source stage applying balance, mono downmix and channel swap to stereo input.
+pub struct ChannelMixer<I> {
+    input: I,
+    settings: Arc<Mutex<ChannelSettings>>,
+    current: ChannelSettings,
+    frame: Vec<Sample>,
+    pos: usize,
+    frames_until_refresh: usize,
+}
+
+impl<I: Source> ChannelMixer<I> {
+    pub fn new(input: I, settings: Arc<Mutex<ChannelSettings>>) -> Self {
+        let current = *settings.lock().unwrap();
+        Self {
+            input,
+            settings,
+            current,
+            frame: Vec::with_capacity(2),
+            pos: 0,
+            frames_until_refresh: REFRESH_FRAMES,
+        }
+    }
+
+    fn refresh(&mut self) {
+        if self.frames_until_refresh == 0 {
+            if let Ok(settings) = self.settings.try_lock() {
+                self.current = *settings;
+            }
+            self.frames_until_refresh = REFRESH_FRAMES;
+        }
+        self.frames_until_refresh -= 1;
+    }
+}
+
+impl<I: Source> Iterator for ChannelMixer<I> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        if self.pos < self.frame.len() {
+            let sample = self.frame[self.pos];
+            self.pos += 1;
+            return Some(sample);
+        }
+
+        let channels = self.input.channels() as usize;
+        self.frame.clear();
+        self.pos = 0;
+
+        for _ in 0..channels {
+            match self.input.next() {
+                Some(sample) => self.frame.push(sample),
+                None => break,
+            }
+        }
+
+        if self.frame.is_empty() {
+            return None;
+        }
+
+        if self.frame.len() == 2 {
+            self.refresh();
+            let (left, right) = self.current.apply(self.frame[0], self.frame[1]);
+            self.frame[0] = left;
+            self.frame[1] = right;
+        }
+
+        self.pos = 1;
+        Some(self.frame[0])
+    }
+}
+
+impl<I: Source> Source for ChannelMixer<I> {
+    fn current_span_len(&self) -> Option<usize> {
+        let pending = self.frame.len() - self.pos;
+        self.input.current_span_len().map(|len| len + pending)
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.frame.clear();
+        self.pos = 0;
+        self.input.try_seek(pos)
+    }
+}
+
+/// Silence bounds for the current track in absolute track time, filled in
+/// by `analyse_silence` on a background thread.
+#[derive(Clone, Copy, Default)]
+pub struct TrimBounds {
+    /// Start of the first audible frame; `None` until the analysis reaches it.
+    pub lead: Option<Duration>,
+    /// Start of trailing silence; `None` until the whole track is analysed.
+    pub end: Option<Duration>,
+}
+
+/// Source stage that skips leading silence and ends the stream at trailing silence.
+///
+/// Positions seen from outside are relative to the first audible frame.
+pub struct SilenceTrim<I> {
+    input: I,
+    bounds: Arc<Mutex<TrimBounds>>,
+    /// Copy of `bounds`, refreshed every `REFRESH_FRAMES` frames.
+    current: TrimBounds,
+    frames_until_refresh: usize,
+    started: bool,
+    frame: Vec<Sample>,
+    pos: usize,
+    frames_read: u64,
+}
+
+impl<I: Source> SilenceTrim<I> {
+    pub fn new(input: I, bounds: Arc<Mutex<TrimBounds>>) -> Self {
+        let current = *bounds.lock().unwrap();
+        Self {
+            input,
+            bounds,
+            current,
+            frames_until_refresh: REFRESH_FRAMES,
+            started: false,
+            frame: Vec::with_capacity(2),
+            pos: 0,
+            frames_read: 0,
+        }
+    }
+
+    /// Continues trimming a track re-opened at `position` (absolute track time).
+    pub fn resume(input: I, bounds: Arc<Mutex<TrimBounds>>, position: Duration) -> Self {
+        let mut trim = Self::new(input, bounds);
+        trim.started = true;
+        trim.frames_read = trim.duration_to_frames(position);
+        trim
+    }
+
+    fn refresh(&mut self) {
+        if self.frames_until_refresh == 0 {
+            if let Ok(bounds) = self.bounds.try_lock() {
+                self.current = *bounds;
+            }
+            self.frames_until_refresh = REFRESH_FRAMES;
+        }
+        self.frames_until_refresh -= 1;
+    }
+
+    /// Jumps past the leading silence once the analysis has found it. Until
+    /// then the (silent) intro simply plays.
+    fn skip_lead(&mut self) {
+        let Some(lead) = self.current.lead else {
+            return;
+        };
+        self.started = true;
+        let lead_frames = self.duration_to_frames(lead);
+        if self.frames_read >= lead_frames {
+            return;
+        }
+        if self.input.try_seek(lead).is_ok() {
+            self.frames_read = lead_frames;
+        } else {
+            // Without seeking the silence has to be decoded, but it is known to be silent.
+            while self.frames_read < lead_frames && self.read_frame() {}
+            self.frame.clear();
+        }
+    }
+
+    fn read_frame(&mut self) -> bool {
+        let channels = self.input.channels() as usize;
+        self.frame.clear();
+        self.pos = 0;
+
+        for _ in 0..channels {
+            match self.input.next() {
+                Some(sample) => self.frame.push(sample),
+                None => break,
+            }
+        }
+
+        if self.frame.is_empty() {
+            return false;
+        }
+        self.frames_read += 1;
+        true
+    }
+
+    fn duration_to_frames(&self, duration: Duration) -> u64 {
+        (duration.as_secs_f64() * self.input.sample_rate() as f64) as u64
+    }
+}
+
+impl<I: Source> Iterator for SilenceTrim<I> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        if self.pos < self.frame.len() {
+            let sample = self.frame[self.pos];
+            self.pos += 1;
+            return Some(sample);
+        }
+
+        self.refresh();
+        if !self.started {
+            self.skip_lead();
+        }
+        // Checked before reading so the playback clock stops at the end bound.
+        if let Some(end) = self.current.end
+            && self.frames_read >= self.duration_to_frames(end)
+        {
+            self.frame.clear();
+            return None;
+        }
+        if !self.read_frame() {
+            return None;
+        }
+
+        self.pos = 1;
+        Some(self.frame[0])
+    }
+}
+
+impl<I: Source> Source for SilenceTrim<I> {
+    fn current_span_len(&self) -> Option<usize> {
+        let pending = self.frame.len() - self.pos;
+        self.input.current_span_len().map(|len| len + pending)
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        match (self.current.lead, self.current.end) {
+            (Some(lead), Some(end)) => Some(end.saturating_sub(lead)),
+            _ => None,
+        }
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.current = *self.bounds.lock().unwrap();
+        let lead = self.current.lead.unwrap_or_default();
+        let target = pos + lead;
+
+        self.input.try_seek(target)?;
+        self.started = true;
+        self.frame.clear();
+        self.pos = 0;
+        self.frames_read = self.duration_to_frames(target);
+        Ok(())
+    }
+}
+
+/// Decodes a whole track to find its silence bounds. The lead is published
+/// as soon as the first audible frame is found so playback can skip to it;
+/// the end follows once the track is decoded. Stops early when `cancel` is set.
+pub fn analyse_silence<I: Source>(
+    input: I,
+    threshold: Sample,
+    bounds: &Mutex<TrimBounds>,
+    cancel: &AtomicBool,
+) {
+    let channels = input.channels().max(1) as u64;
+    let sample_rate = input.sample_rate() as f64;
+    let frame_time = |index: u64| Duration::from_secs_f64((index / channels) as f64 / sample_rate);
+
+    let mut last_audible = None;
+    for (index, sample) in input.enumerate() {
+        let index = index as u64;
+        if index.is_multiple_of(REFRESH_FRAMES as u64 * channels) && cancel.load(Ordering::Relaxed)
+        {
+            return;
+        }
+        if sample.abs() > threshold {
+            if last_audible.is_none() {
+                bounds.lock().unwrap().lead = Some(frame_time(index));
+            }
+            last_audible = Some(index);
+        }
+    }
+
+    let mut bounds = bounds.lock().unwrap();
+    match last_audible {
+        Some(index) => bounds.end = Some(frame_time(index + channels)),
+        // Nothing audible at all: end the track right away.
+        None => {
+            bounds.lead = Some(Duration::ZERO);
+            bounds.end = Some(Duration::ZERO);
+        }
+    }
+}
+
+/// Playback position shared between the audio thread and the UI, in nanoseconds.
+#[derive(Default)]
+pub struct PlaybackClock(AtomicU64);
+
+impl PlaybackClock {
+    pub fn position(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, position: Duration) {
+        self.0.store(position.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Source stage that advances a `PlaybackClock` by counting decoded frames.
+///
+/// Sits directly on the decoder so the clock follows absolute track time,
+/// independent of later stages dropping or buffering samples.
+pub struct PositionTracker<I> {
+    input: I,
+    clock: Arc<PlaybackClock>,
+    base: Duration,
+    frames: u64,
+    sample_rate: SampleRate,
+    channel: ChannelCount,
+}
+
+impl<I: Source> PositionTracker<I> {
+    pub fn new(input: I, clock: Arc<PlaybackClock>) -> Self {
+        clock.set(Duration::ZERO);
+        let sample_rate = input.sample_rate();
+        Self {
+            input,
+            clock,
+            base: Duration::ZERO,
+            frames: 0,
+            sample_rate,
+            channel: 0,
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.base + Duration::from_secs_f64(self.frames as f64 / self.sample_rate.max(1) as f64)
+    }
+}
+
+impl<I: Source> Iterator for PositionTracker<I> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        if self.channel == 0 && self.input.sample_rate() != self.sample_rate {
+            self.base = self.elapsed();
+            self.frames = 0;
+            self.sample_rate = self.input.sample_rate();
+        }
+
+        let sample = self.input.next()?;
+
+        self.channel += 1;
+        if self.channel >= self.input.channels().max(1) {
+            self.channel = 0;
+            self.frames += 1;
+            self.clock.set(self.elapsed());
+        }
+
+        Some(sample)
+    }
+}
+
+impl<I: Source> Source for PositionTracker<I> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.input.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.base = pos;
+        self.frames = 0;
+        self.channel = 0;
+        self.sample_rate = self.input.sample_rate();
+        self.clock.set(pos);
+        Ok(())
+    }
+}
@@ -1,14 +1,14 @@
 use std::time::Duration;
 
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 
-use crate::app::App;
+use crate::app::{App, PromptKind, SortPick, View};
 
 pub fn handle_events(app: &mut App) -> std::io::Result<bool> {
     if event::poll(Duration::from_millis(33))? {
         if let Event::Key(key) = event::read()? {
             if key.kind == KeyEventKind::Press {
-                handle_key(app, key.code);
+                handle_key(app, key);
             }
         }
         return Ok(true);
@@ -16,10 +16,38 @@ pub fn handle_events(app: &mut App) -> std::io::Result<bool> {
     Ok(false)
 }
 
-fn handle_key(app: &mut App, code: KeyCode) {
+fn handle_key(app: &mut App, key: KeyEvent) {
+    let code = key.code;
+    if app.prompt.is_some() {
+        match code {
+            KeyCode::Enter => app.submit_prompt(),
+            KeyCode::Esc => app.cancel_prompt(),
+            KeyCode::Backspace => app.prompt_backspace(),
+            KeyCode::Char(c) => app.prompt_input(c),
+            _ => {}
+        }
+        return;
+    }
+
+    if app.pending_rating {
+        app.pending_rating = false;
+        if let KeyCode::Char(c @ '0'..='5') = code {
+            app.rate_selected(c as u8 - b'0');
+        }
+        return;
+    }
+
+    if app.pending_sort.is_some() {
+        match code {
+            KeyCode::Char(c) => app.pick_sort_column(c),
+            _ => app.pending_sort = None,
+        }
+        return;
+    }
+
     if app.show_help {
         match code {
-            KeyCode::Char('h') | KeyCode::Esc => {
+            KeyCode::Char('h') | KeyCode::Char('?') | KeyCode::Esc => {
                 app.toggle_help();
                 return;
             }
@@ -27,6 +55,10 @@ fn handle_key(app: &mut App, code: KeyCode) {
         }
     }
 
+    if app.show_stats && handle_stats_key(app, code) {
+        return;
+    }
+
     if app.show_lyrics {
         match code {
             KeyCode::Char('l') | KeyCode::Esc => {
@@ -37,11 +69,34 @@ fn handle_key(app: &mut App, code: KeyCode) {
         }
     }
 
+    if key.modifiers.contains(KeyModifiers::CONTROL) && code == KeyCode::Char('r') {
+        app.redo();
+        return;
+    }
+
+    if !app.show_help && !app.show_stats {
+        let handled = match app.view {
+            View::Playlist => handle_playlist_key(app, code),
+            View::Library => handle_library_key(app, code),
+            View::Folders => handle_folders_key(app, code),
+            View::Queue => handle_queue_key(app, code),
+            View::Playlists => handle_playlists_key(app, code),
+        };
+        if handled {
+            return;
+        }
+    }
+
     match code {
         KeyCode::Char('q') => app.quit(),
-        KeyCode::Char('h') => app.toggle_help(),
+        KeyCode::Tab => app.cycle_view(),
+        KeyCode::Char('h') | KeyCode::Char('?') => app.toggle_help(),
         KeyCode::Char('l') => app.toggle_lyrics(),
-        KeyCode::Char('o') => app.cycle_sort_mode(),
+        KeyCode::Char('H') => app.toggle_stats(),
+        KeyCode::Char('o') => app.start_sort(SortPick::Replace),
+        KeyCode::Char('O') => app.start_sort(SortPick::Append),
+        KeyCode::Char('/') => app.start_search(),
+        KeyCode::Char('u') => app.undo(),
 
         KeyCode::Char(' ') => app.toggle_pause(),
         KeyCode::Char('s') => app.stop(),
@@ -62,6 +117,11 @@ fn handle_key(app: &mut App, code: KeyCode) {
             }
         }
 
+        KeyCode::Char('a') => app.enqueue_selected(),
+        KeyCode::Char('A') => app.enqueue_selected_next(),
+        KeyCode::Char('C') => app.clear_queue(),
+        KeyCode::Char('p') => app.add_selected_to_playlist(),
+
         KeyCode::Char('+') | KeyCode::Char('=') => app.change_volume(true),
         KeyCode::Char('-') => app.change_volume(false),
 
@@ -69,9 +129,18 @@ fn handle_key(app: &mut App, code: KeyCode) {
         KeyCode::Left => app.seek_backward(),
 
         KeyCode::Char('r') => app.check_repeat_mode(),
+        KeyCode::Char('c') => app.toggle_stop_after_current(),
+        KeyCode::Char('T') => app.start_prompt(PromptKind::StopAfter),
         KeyCode::Char('z') => app.toggle_shuffle(),
+        KeyCode::Char('Z') => app.cycle_shuffle_mode(),
+        KeyCode::Char('i') => app.toggle_radio(),
 
         KeyCode::Char('m') => app.toggle_mute(),
+        KeyCode::Char(',') => app.adjust_balance(false),
+        KeyCode::Char('.') => app.adjust_balance(true),
+        KeyCode::Char('M') => app.toggle_mono(),
+        KeyCode::Char('x') => app.toggle_swap_channels(),
+        KeyCode::Char('t') => app.toggle_trim_silence(),
 
         KeyCode::Char('[') => app.play_previous_track(),
         KeyCode::Char(']') => app.play_next_track(),
@@ -93,3 +162,100 @@ fn handle_key(app: &mut App, code: KeyCode) {
         _ => {}
     }
 }
+
+fn handle_stats_key(app: &mut App, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Char('H') | KeyCode::Esc => app.toggle_stats(),
+        KeyCode::Char('p') => app.cycle_stats_period(),
+        KeyCode::Down | KeyCode::Char('j') => app.select_history(true),
+        KeyCode::Up | KeyCode::Char('k') => app.select_history(false),
+        KeyCode::Enter => app.replay_history_selected(),
+        _ => return false,
+    }
+    true
+}
+
+fn handle_playlist_key(app: &mut App, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Enter => app.play_marked(),
+        KeyCode::Char('v') => app.toggle_visual(),
+        KeyCode::Char('V') => app.toggle_mark(),
+        KeyCode::Char('*') => app.start_rating(),
+        KeyCode::Char('f') => app.toggle_favorite(),
+        KeyCode::Char('{') => app.focus_column(-1),
+        KeyCode::Char('}') => app.focus_column(1),
+        KeyCode::Char('<') => app.resize_column(-1),
+        KeyCode::Char('>') => app.resize_column(1),
+        KeyCode::Esc if app.has_marks() => app.clear_marks(),
+        KeyCode::Char('n') if app.filter.is_some() => app.search_next(true),
+        KeyCode::Char('N') if app.filter.is_some() => app.search_next(false),
+        KeyCode::Esc if app.filter.is_some() => app.clear_filter(),
+        _ => return false,
+    }
+    true
+}
+
+fn handle_library_key(app: &mut App, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Char('h') | KeyCode::Left => app.library_move_column(false),
+        KeyCode::Char('l') | KeyCode::Right => app.library_move_column(true),
+        KeyCode::Enter => app.play_library_selection(),
+        KeyCode::Char('a') => app.enqueue_library_selection(false),
+        KeyCode::Char('A') => app.enqueue_library_selection(true),
+        _ => return false,
+    }
+    true
+}
+
+fn handle_folders_key(app: &mut App, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Enter => app.activate_folder_row(),
+        KeyCode::Char('l') | KeyCode::Right => app.expand_folder(true),
+        KeyCode::Char('h') | KeyCode::Left => app.expand_folder(false),
+        KeyCode::Char('P') => app.play_folder(),
+        KeyCode::Char('a') => app.enqueue_folder(false),
+        KeyCode::Char('A') => app.enqueue_folder(true),
+        _ => return false,
+    }
+    true
+}
+
+fn handle_queue_key(app: &mut App, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Enter => app.play_queue_selected(),
+        KeyCode::Char('d') | KeyCode::Delete => app.remove_queue_selected(),
+        KeyCode::Char('v') => app.toggle_visual(),
+        KeyCode::Char('V') => app.toggle_mark(),
+        KeyCode::Esc if app.has_marks() => app.clear_marks(),
+        KeyCode::Char('K') => app.move_queue_selected(true),
+        KeyCode::Char('J') => app.move_queue_selected(false),
+        _ => return false,
+    }
+    true
+}
+
+fn handle_playlists_key(app: &mut App, code: KeyCode) -> bool {
+    if app.open_playlist.is_some() {
+        match code {
+            KeyCode::Enter | KeyCode::Char('P') => app.play_selected_playlist(),
+            KeyCode::Esc | KeyCode::Backspace => app.close_playlist(),
+            KeyCode::Char('d') | KeyCode::Delete => app.remove_playlist_entry(),
+            KeyCode::Char('K') => app.move_playlist_entry(true),
+            KeyCode::Char('J') => app.move_playlist_entry(false),
+            _ => return false,
+        }
+    } else {
+        match code {
+            KeyCode::Enter => app.open_selected_playlist(),
+            KeyCode::Char('P') => app.play_selected_playlist(),
+            KeyCode::Char('n') => app.start_prompt(PromptKind::NewPlaylist),
+            KeyCode::Char('S') => app.start_prompt(PromptKind::NewSmart),
+            KeyCode::Char('R') => app.start_prompt(PromptKind::Rename),
+            KeyCode::Char('I') => app.start_prompt(PromptKind::Import),
+            KeyCode::Char('E') => app.start_prompt(PromptKind::Export),
+            KeyCode::Char('D') => app.delete_selected_playlist(),
+            _ => return false,
+        }
+    }
+    true
+}
@@ -0,0 +1,128 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::scanner::Track;
+
+pub struct FolderNode {
+    pub name: String,
+    pub path: PathBuf,
+    pub folders: Vec<FolderNode>,
+    /// Library indices of the files directly in this folder.
+    pub tracks: Vec<usize>,
+}
+
+impl FolderNode {
+    fn new(name: String, path: PathBuf) -> Self {
+        Self {
+            name,
+            path,
+            folders: Vec::new(),
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Every track in this folder and its subfolders, in display order.
+    pub fn all_tracks(&self) -> Vec<usize> {
+        let mut tracks = Vec::new();
+        self.collect_tracks(&mut tracks);
+        tracks
+    }
+
+    fn collect_tracks(&self, tracks: &mut Vec<usize>) {
+        for folder in &self.folders {
+            folder.collect_tracks(tracks);
+        }
+        tracks.extend(&self.tracks);
+    }
+
+    fn find(&self, path: &Path) -> Option<&FolderNode> {
+        if self.path == path {
+            return Some(self);
+        }
+        self.folders
+            .iter()
+            .filter(|f| path.starts_with(&f.path))
+            .find_map(|f| f.find(path))
+    }
+
+    fn sort(&mut self, tracks: &[Track]) {
+        self.folders.sort_by_key(|f| f.name.to_lowercase());
+        self.tracks.sort_by_key(|&i| tracks[i].path.clone());
+        for folder in &mut self.folders {
+            folder.sort(tracks);
+        }
+    }
+}
+
+/// A visible line of the folder view.
+pub enum FolderRow {
+    Folder { path: PathBuf, depth: usize },
+    Track { index: usize, depth: usize },
+}
+
+/// Directory tree of the scanned library, rooted at the music directory.
+pub struct FolderTree {
+    pub root: FolderNode,
+    pub expanded: HashSet<PathBuf>,
+}
+
+impl FolderTree {
+    pub fn build(music_dir: &Path, tracks: &[Track]) -> Self {
+        let mut root = FolderNode::new(String::new(), music_dir.to_path_buf());
+
+        for (index, track) in tracks.iter().enumerate() {
+            let relative = track
+                .path
+                .parent()
+                .and_then(|dir| dir.strip_prefix(music_dir).ok())
+                .unwrap_or(Path::new(""));
+
+            let mut node = &mut root;
+            for component in relative.iter() {
+                let name = component.to_string_lossy().to_string();
+                let pos = match node.folders.iter().position(|f| f.name == name) {
+                    Some(pos) => pos,
+                    None => {
+                        let path = node.path.join(component);
+                        node.folders.push(FolderNode::new(name, path));
+                        node.folders.len() - 1
+                    }
+                };
+                node = &mut node.folders[pos];
+            }
+            node.tracks.push(index);
+        }
+
+        root.sort(tracks);
+        Self {
+            root,
+            expanded: HashSet::new(),
+        }
+    }
+
+    pub fn folder(&self, path: &Path) -> Option<&FolderNode> {
+        self.root.find(path)
+    }
+
+    /// Folders and files currently visible, depth-first with folders first.
+    pub fn rows(&self) -> Vec<FolderRow> {
+        let mut rows = Vec::new();
+        self.push_rows(&self.root, 0, &mut rows);
+        rows
+    }
+
+    fn push_rows(&self, node: &FolderNode, depth: usize, rows: &mut Vec<FolderRow>) {
+        for folder in &node.folders {
+            rows.push(FolderRow::Folder {
+                path: folder.path.clone(),
+                depth,
+            });
+            if self.expanded.contains(&folder.path) {
+                self.push_rows(folder, depth + 1, rows);
+            }
+        }
+        for &index in &node.tracks {
+            rows.push(FolderRow::Track { index, depth });
+        }
+    }
+}
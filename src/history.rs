@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Plays listened to for less than this share of the track count as skipped.
+const SKIP_THRESHOLD: f64 = 0.5;
+
+/// Number of entries in each top list.
+pub const TOP_COUNT: usize = 10;
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// One play of a track, appended to the history file when it ends.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayRecord {
+    pub path: PathBuf,
+    pub artist: String,
+    pub title: String,
+    #[serde(default)]
+    pub album: Option<String>,
+    /// Unix time the play started, in seconds.
+    pub started_at: u64,
+    pub listened_secs: u64,
+    pub duration_secs: u64,
+    pub skipped: bool,
+}
+
+impl PlayRecord {
+    pub fn display_name(&self) -> String {
+        format!("{} - {}", self.artist, self.title)
+    }
+}
+
+/// Whether a play that ended after `listened_secs` counts as skipped.
+pub fn is_skip(listened_secs: u64, duration_secs: u64, completed: bool) -> bool {
+    !completed && (listened_secs as f64) < duration_secs as f64 * SKIP_THRESHOLD
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StatsPeriod {
+    Week,
+    Month,
+    AllTime,
+}
+
+impl StatsPeriod {
+    pub fn label(&self) -> &'static str {
+        match self {
+            StatsPeriod::Week => "Last 7 days",
+            StatsPeriod::Month => "Last 30 days",
+            StatsPeriod::AllTime => "All time",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            StatsPeriod::Week => StatsPeriod::Month,
+            StatsPeriod::Month => StatsPeriod::AllTime,
+            StatsPeriod::AllTime => StatsPeriod::Week,
+        }
+    }
+
+    fn since(&self, now: u64) -> u64 {
+        match self {
+            StatsPeriod::Week => now.saturating_sub(7 * DAY_SECS),
+            StatsPeriod::Month => now.saturating_sub(30 * DAY_SECS),
+            StatsPeriod::AllTime => 0,
+        }
+    }
+}
+
+/// Play counts and listening time for one period.
+pub struct Stats {
+    pub period: StatsPeriod,
+    pub plays: usize,
+    pub skips: usize,
+    pub listened_secs: u64,
+    pub top_artists: Vec<(String, usize)>,
+    pub top_albums: Vec<(String, usize)>,
+    pub top_tracks: Vec<(String, usize)>,
+}
+
+/// Append-only play log stored as JSON lines in the tune data directory.
+#[derive(Default)]
+pub struct History {
+    pub records: Vec<PlayRecord>,
+}
+
+impl History {
+    pub fn load() -> Self {
+        let records = history_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { records }
+    }
+
+    pub fn record(&mut self, record: PlayRecord) {
+        if let Some(path) = history_path()
+            && let Ok(line) = serde_json::to_string(&record)
+            && let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path)
+        {
+            writeln!(file, "{}", line).ok();
+        }
+        self.records.push(record);
+    }
+
+    /// When each track was last played, as Unix time.
+    pub fn last_played(&self) -> HashMap<&Path, u64> {
+        let mut last_played = HashMap::new();
+        for record in &self.records {
+            let played = last_played.entry(record.path.as_path()).or_default();
+            *played = record.started_at.max(*played);
+        }
+        last_played
+    }
+
+    /// Plays of each track that were not skipped.
+    pub fn play_counts(&self) -> HashMap<&Path, u32> {
+        let mut counts = HashMap::new();
+        for record in self.records.iter().filter(|r| !r.skipped) {
+            *counts.entry(record.path.as_path()).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Most recent plays first.
+    pub fn recent(&self) -> impl Iterator<Item = &PlayRecord> {
+        self.records.iter().rev()
+    }
+
+    pub fn stats(&self, period: StatsPeriod) -> Stats {
+        let since = period.since(now_secs());
+        let mut artists: HashMap<String, (String, usize)> = HashMap::new();
+        let mut albums: HashMap<String, (String, usize)> = HashMap::new();
+        let mut tracks: HashMap<&PathBuf, (String, usize)> = HashMap::new();
+        let mut stats = Stats {
+            period,
+            plays: 0,
+            skips: 0,
+            listened_secs: 0,
+            top_artists: Vec::new(),
+            top_albums: Vec::new(),
+            top_tracks: Vec::new(),
+        };
+
+        for record in self.records.iter().filter(|r| r.started_at >= since) {
+            stats.listened_secs += record.listened_secs;
+            if record.skipped {
+                stats.skips += 1;
+                continue;
+            }
+            stats.plays += 1;
+
+            artists
+                .entry(record.artist.to_lowercase())
+                .or_insert_with(|| (record.artist.clone(), 0))
+                .1 += 1;
+            if let Some(album) = &record.album {
+                let name = format!("{} - {}", record.artist, album);
+                albums
+                    .entry(name.to_lowercase())
+                    .or_insert_with(|| (name, 0))
+                    .1 += 1;
+            }
+            tracks
+                .entry(&record.path)
+                .or_insert_with(|| (record.display_name(), 0))
+                .1 += 1;
+        }
+
+        stats.top_artists = top(artists.into_values());
+        stats.top_albums = top(albums.into_values());
+        stats.top_tracks = top(tracks.into_values());
+        stats
+    }
+}
+
+fn top(counts: impl Iterator<Item = (String, usize)>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts.collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(TOP_COUNT);
+    counts
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Short relative age such as `5m ago` or `3d ago`.
+pub fn format_age(timestamp: u64) -> String {
+    let age = now_secs().saturating_sub(timestamp);
+    match age {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", age / 60),
+        3600..DAY_SECS => format!("{}h ago", age / 3600),
+        _ => format!("{}d ago", age / DAY_SECS),
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    let mut path = dirs::data_dir()?;
+    path.push("tune");
+    fs::create_dir_all(&path).ok();
+    path.push("history.jsonl");
+    Some(path)
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use crate::collate::Collator;
+use crate::scanner::Track;
+
+pub const UNKNOWN_ALBUM: &str = "Unknown Album";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LibraryColumn {
+    Artists,
+    Albums,
+    Tracks,
+}
+
+pub struct AlbumNode {
+    pub name: String,
+    /// ALBUMSORT tag of the album's first track.
+    pub sort_tag: Option<String>,
+    /// Library indices, in track-number order.
+    pub tracks: Vec<usize>,
+}
+
+pub struct ArtistNode {
+    pub name: String,
+    /// ALBUMARTISTSORT or ARTISTSORT tag matching `name`.
+    pub sort_tag: Option<String>,
+    pub albums: Vec<AlbumNode>,
+}
+
+impl ArtistNode {
+    pub fn tracks(&self) -> Vec<usize> {
+        self.albums
+            .iter()
+            .flat_map(|album| album.tracks.iter().copied())
+            .collect()
+    }
+}
+
+/// Artist → album → track tree, grouped case-insensitively by album artist.
+#[derive(Default)]
+pub struct Library {
+    pub artists: Vec<ArtistNode>,
+}
+
+impl Library {
+    pub fn build(tracks: &[Track], collator: &Collator) -> Self {
+        let mut artists: Vec<ArtistNode> = Vec::new();
+        let mut artist_lookup: HashMap<String, usize> = HashMap::new();
+        let mut album_lookup: HashMap<(usize, String), usize> = HashMap::new();
+
+        for (index, track) in tracks.iter().enumerate() {
+            let (artist_name, artist_sort) = match track
+                .album_artist
+                .as_deref()
+                .filter(|a| !a.trim().is_empty())
+            {
+                Some(album_artist) => (album_artist.trim(), &track.album_artist_sort),
+                None => (track.artist.trim(), &track.artist_sort),
+            };
+            let artist = *artist_lookup
+                .entry(artist_name.to_lowercase())
+                .or_insert_with(|| {
+                    artists.push(ArtistNode {
+                        name: artist_name.to_string(),
+                        sort_tag: artist_sort.clone(),
+                        albums: Vec::new(),
+                    });
+                    artists.len() - 1
+                });
+
+            let album_name = track
+                .album
+                .as_deref()
+                .filter(|a| !a.trim().is_empty())
+                .unwrap_or(UNKNOWN_ALBUM)
+                .trim();
+            let albums = &mut artists[artist].albums;
+            let album = *album_lookup
+                .entry((artist, album_name.to_lowercase()))
+                .or_insert_with(|| {
+                    albums.push(AlbumNode {
+                        name: album_name.to_string(),
+                        sort_tag: track.album_sort.clone(),
+                        tracks: Vec::new(),
+                    });
+                    albums.len() - 1
+                });
+            albums[album].tracks.push(index);
+        }
+
+        for artist in &mut artists {
+            for album in &mut artist.albums {
+                album.tracks.sort_by(|&a, &b| {
+                    let (a, b) = (&tracks[a], &tracks[b]);
+                    a.track_number
+                        .unwrap_or(u32::MAX)
+                        .cmp(&b.track_number.unwrap_or(u32::MAX))
+                        .then_with(|| {
+                            collator.compare(
+                                collator.sort_name(&a.title, a.title_sort.as_deref()),
+                                collator.sort_name(&b.title, b.title_sort.as_deref()),
+                            )
+                        })
+                });
+            }
+            artist.albums.sort_by(|a, b| {
+                collator.compare(
+                    collator.sort_name(&a.name, a.sort_tag.as_deref()),
+                    collator.sort_name(&b.name, b.sort_tag.as_deref()),
+                )
+            });
+        }
+        artists.sort_by(|a, b| {
+            collator.compare(
+                collator.sort_name(&a.name, a.sort_tag.as_deref()),
+                collator.sort_name(&b.name, b.sort_tag.as_deref()),
+            )
+        });
+
+        Self { artists }
+    }
+
+    /// The album containing the library track `index`.
+    pub fn album_of(&self, index: usize) -> Option<&AlbumNode> {
+        self.artists
+            .iter()
+            .flat_map(|artist| &artist.albums)
+            .find(|album| album.tracks.contains(&index))
+    }
+
+    pub fn find_artist(&self, name: &str) -> Option<usize> {
+        let name = name.to_lowercase();
+        self.artists
+            .iter()
+            .position(|a| a.name.to_lowercase() == name)
+    }
+}
@@ -1,10 +1,26 @@
+mod advance;
 mod app;
+mod collate;
+mod columns;
 mod config;
+mod dsp;
 mod event;
+mod folders;
+mod history;
+mod library;
 mod player;
+mod playlist;
+mod radio;
+mod ratings;
 mod scanner;
+mod scrobble;
+mod search;
+mod shuffle;
+mod smart;
 mod state;
+mod submit;
 mod ui;
+mod undo;
 
 use std::io;
 
@@ -33,10 +49,11 @@ fn main() -> io::Result<()> {
     let music_dir = std::env::args().nth(1).map(std::path::PathBuf::from);
     let config = Config::new(music_dir);
 
-    let tracks = scan_music_directory(&config);
+    let scan = scan_music_directory(&config);
 
-    let mut app = App::new(tracks);
+    let mut app = App::new(scan.tracks, config);
     app.sort_tracks();
+    app.import_playlists(&scan.playlist_files);
 
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -1,7 +1,40 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use rodio::{Decoder, OutputStream, OutputStreamBuilder, Sink};
+use rodio::{Decoder, OutputStream, OutputStreamBuilder, Sink, Source};
+
+use crate::config::MIN_VOLUME_STEP;
+use crate::dsp::{
+    ChannelMixer, ChannelSettings, PlaybackClock, PositionTracker, SilenceTrim, TrimBounds,
+    analyse_silence,
+};
+
+/// A re-opened track already decoded up to its seek target.
+type Reopened = PositionTracker<Decoder<std::io::Cursor<Vec<u8>>>>;
+
+/// Dynamic range covered by volume levels between 0.0 and 1.0.
+const VOLUME_RANGE_DB: f32 = 60.0;
+
+/// Maps a perceptual volume level (1.0 = unity gain) to a linear amplitude.
+pub fn level_to_gain(level: f32) -> f32 {
+    if level <= 0.0 {
+        0.0
+    } else {
+        10f32.powf(VOLUME_RANGE_DB * (level - 1.0) / 20.0)
+    }
+}
+
+/// Inverse of `level_to_gain`, used to migrate linear amplitudes.
+pub fn gain_to_level(gain: f32) -> f32 {
+    if gain <= 0.0 {
+        0.0
+    } else {
+        (1.0 + 20.0 * gain.log10() / VOLUME_RANGE_DB).max(0.0)
+    }
+}
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum PlaybackState {
@@ -15,10 +48,23 @@ pub struct Player {
     sink: Sink,
     pub state: PlaybackState,
     pub current_track: Option<String>,
-    elapsed: Arc<Mutex<Duration>>,
+    current_path: Option<PathBuf>,
+    clock: Arc<PlaybackClock>,
     pub volume: f32,
+    pub volume_step: f32,
+    pub max_volume: f32,
     pub muted: bool,
     pub pre_mute_volume: f32,
+    channel_settings: Arc<Mutex<ChannelSettings>>,
+    pub trim_silence: bool,
+    pub silence_threshold: f32,
+    trim_bounds: Option<Arc<Mutex<TrimBounds>>>,
+    /// Set to stop the silence analysis of the previous track.
+    analysis_cancel: Arc<AtomicBool>,
+    /// Fallback seek being decoded on a helper thread, with its target.
+    pending_seek: Option<Receiver<Result<(Reopened, Duration), String>>>,
+    /// Set to abandon the pending fallback seek.
+    seek_cancel: Arc<AtomicBool>,
 }
 
 impl Player {
@@ -33,39 +79,137 @@ impl Player {
             sink,
             state: PlaybackState::Stopped,
             current_track: None,
-            elapsed: Arc::new(Mutex::new(Duration::ZERO)),
+            current_path: None,
+            clock: Arc::new(PlaybackClock::default()),
             volume: 1.0,
+            volume_step: 0.05,
+            max_volume: 1.0,
             muted: false,
             pre_mute_volume: 1.0,
+            channel_settings: Arc::new(Mutex::new(ChannelSettings::default())),
+            trim_silence: false,
+            silence_threshold: 0.001,
+            trim_bounds: None,
+            analysis_cancel: Arc::new(AtomicBool::new(false)),
+            pending_seek: None,
+            seek_cancel: Arc::new(AtomicBool::new(false)),
         })
     }
 
-    pub fn play(&mut self, path: &std::path::Path, track_name: &str) -> Result<(), String> {
+    pub fn play(&mut self, path: &Path, track_name: &str) -> Result<(), String> {
         self.stop();
 
         let file_bytes = std::fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
-        let byte_len = file_bytes.len() as u64;
-        let cursor = std::io::Cursor::new(file_bytes);
 
-        let source = Decoder::builder()
-            .with_data(cursor)
-            .with_seekable(true)
-            .with_byte_len(byte_len)
-            .build()
-            .map_err(|e| format!("Failed to decode: {}", e))?;
+        self.clock = Arc::new(PlaybackClock::default());
+        let source = PositionTracker::new(decode(file_bytes)?, self.clock.clone());
 
-        *self.elapsed.lock().unwrap() = Duration::ZERO;
+        if self.trim_silence {
+            let bounds = Arc::new(Mutex::new(TrimBounds::default()));
+            self.spawn_silence_analysis(path, bounds.clone());
+            self.trim_bounds = Some(bounds);
+        }
 
-        self.sink.append(source);
-        self.sink.set_volume(self.volume);
+        self.append_chain(source, None);
         self.sink.play();
 
         self.state = PlaybackState::Playing;
         self.current_track = Some(track_name.to_string());
+        self.current_path = Some(path.to_path_buf());
+
+        Ok(())
+    }
+
+    /// Appends the DSP stages after the position tracker and hands the result to the sink.
+    fn append_chain<S: Source + Send + 'static>(&mut self, source: S, resume_at: Option<Duration>) {
+        match &self.trim_bounds {
+            Some(bounds) => {
+                let source = match resume_at {
+                    Some(position) => SilenceTrim::resume(source, bounds.clone(), position),
+                    None => SilenceTrim::new(source, bounds.clone()),
+                };
+                self.sink
+                    .append(ChannelMixer::new(source, self.channel_settings.clone()));
+            }
+            None => self
+                .sink
+                .append(ChannelMixer::new(source, self.channel_settings.clone())),
+        }
+        self.sink.set_volume(level_to_gain(self.volume));
+    }
+
+    /// Replaces the sink with a freshly decoded copy of the current track,
+    /// skipping forward to `position`. Used when the decoder cannot seek.
+    /// The skip decodes everything before `position`, so it runs on a helper
+    /// thread and `poll_seek` picks up the result.
+    fn reopen_at(&mut self, position: Duration) -> Result<(), String> {
+        let path = self.current_path.clone().ok_or("No track loaded")?;
+
+        self.seek_cancel.store(true, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.seek_cancel = cancel.clone();
+
+        self.reset_sink();
+        self.clock = Arc::new(PlaybackClock::default());
+        let clock = self.clock.clone();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = std::fs::read(&path)
+                .map_err(|e| format!("Failed to read file: {}", e))
+                .and_then(decode)
+                .map(|decoder| {
+                    let mut source = PositionTracker::new(decoder, clock.clone());
+                    // The clock only moves on whole frames, so this stops on a frame boundary.
+                    let mut samples = 0u32;
+                    while clock.position() < position && source.next().is_some() {
+                        samples = samples.wrapping_add(1);
+                        if samples.is_multiple_of(4096) && cancel.load(Ordering::Relaxed) {
+                            break;
+                        }
+                    }
+                    (source, position)
+                });
+            if !cancel.load(Ordering::Relaxed) {
+                sender.send(result).ok();
+            }
+        });
+        self.pending_seek = Some(receiver);
+        Ok(())
+    }
+
+    /// Whether a fallback seek is still decoding up to its target.
+    pub fn is_seeking(&self) -> bool {
+        self.pending_seek.is_some()
+    }
+
+    /// Starts playback from a finished fallback seek. Returns its error, if any.
+    pub fn poll_seek(&mut self) -> Result<(), String> {
+        let Some(receiver) = &self.pending_seek else {
+            return Ok(());
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return Ok(()),
+            Err(mpsc::TryRecvError::Disconnected) => Err("Seek was interrupted".to_string()),
+        };
+        self.pending_seek = None;
 
+        let (source, position) = result.map_err(|e| format!("Seek failed: {}", e))?;
+        self.append_chain(source, Some(position));
+        if self.state == PlaybackState::Paused {
+            self.sink.pause();
+        } else {
+            self.sink.play();
+        }
         Ok(())
     }
 
+    fn reset_sink(&mut self) {
+        self.sink.stop();
+        self.sink = Sink::connect_new(self._stream.mixer());
+        self.sink.set_volume(level_to_gain(self.volume));
+    }
+
     pub fn toggle_pause(&mut self) {
         match self.state {
             PlaybackState::Playing => {
@@ -81,35 +225,85 @@ impl Player {
     }
 
     pub fn stop(&mut self) {
-        self.sink.stop();
         self.state = PlaybackState::Stopped;
         self.current_track = None;
-        *self.elapsed.lock().unwrap() = Duration::ZERO;
+        self.current_path = None;
+        self.trim_bounds = None;
+        self.analysis_cancel.store(true, Ordering::Relaxed);
+        self.seek_cancel.store(true, Ordering::Relaxed);
+        self.pending_seek = None;
+        self.clock = Arc::new(PlaybackClock::default());
 
-        self.sink = Sink::connect_new(self._stream.mixer());
-        self.sink.set_volume(self.volume);
+        self.reset_sink();
     }
 
     pub fn is_finished(&self) -> bool {
-        self.sink.empty() && self.state == PlaybackState::Playing
+        self.sink.empty() && self.state == PlaybackState::Playing && self.pending_seek.is_none()
     }
 
+    /// Position in absolute track time, counted from the samples actually decoded.
     pub fn position(&self) -> Duration {
-        self.sink.get_pos()
+        self.clock.position()
+    }
+
+    /// Audible part of the current track when silence trimming is active.
+    pub fn trim_window(&self) -> Option<(Duration, Option<Duration>)> {
+        let bounds = *self.trim_bounds.as_ref()?.lock().unwrap();
+        Some((bounds.lead.unwrap_or_default(), bounds.end))
+    }
+
+    /// Finds the silence bounds on a separate decoder reading straight from
+    /// the file. The analysis is cancelled when the track stops or changes.
+    fn spawn_silence_analysis(&mut self, path: &Path, bounds: Arc<Mutex<TrimBounds>>) {
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.analysis_cancel = cancel.clone();
+        let threshold = self.silence_threshold;
+        let path = path.to_path_buf();
+        std::thread::spawn(move || {
+            let Ok(file) = std::fs::File::open(&path) else {
+                return;
+            };
+            let byte_len = file.metadata().map(|m| m.len()).ok();
+            let mut builder = Decoder::builder()
+                .with_data(std::io::BufReader::new(file))
+                .with_seekable(true);
+            if let Some(len) = byte_len {
+                builder = builder.with_byte_len(len);
+            }
+            if let Ok(source) = builder.build() {
+                analyse_silence(source, threshold, &bounds, &cancel);
+            }
+        });
     }
 
+    /// Sets the volume level, allowing amplification up to `max_volume`.
     pub fn set_volume(&mut self, volume: f32) {
-        let rounded_volume = (volume * 10.0).round() / 10.0;
-        self.volume = rounded_volume.clamp(0.0, 1.0);
-        self.sink.set_volume(self.volume);
+        let step = self.volume_step.max(MIN_VOLUME_STEP);
+        let rounded_volume = (volume / step).round() * step;
+        self.volume = rounded_volume.clamp(0.0, self.max_volume.max(1.0));
+        self.sink.set_volume(level_to_gain(self.volume));
+    }
+
+    /// Enables software gain above 100%, up to `max_db` of headroom.
+    pub fn set_max_volume_db(&mut self, max_db: f32) {
+        self.max_volume = 1.0 + max_db.max(0.0) / VOLUME_RANGE_DB;
+    }
+
+    /// Current gain in decibels, or `None` when silent.
+    pub fn volume_db(&self) -> Option<f32> {
+        if self.volume <= 0.0 {
+            None
+        } else {
+            Some(VOLUME_RANGE_DB * (self.volume - 1.0))
+        }
     }
 
     pub fn increase_volume(&mut self) {
-        self.set_volume(self.volume + 0.1);
+        self.set_volume(self.volume + self.volume_step);
     }
 
     pub fn decrease_volume(&mut self) {
-        self.set_volume(self.volume - 0.1);
+        self.set_volume(self.volume - self.volume_step);
     }
 
     pub fn toggle_mute(&mut self) {
@@ -123,9 +317,68 @@ impl Player {
         }
     }
 
-    pub fn seek(&mut self, duration: Duration) {
-        self.sink.try_seek(duration).ok();
+    pub fn channel_settings(&self) -> ChannelSettings {
+        *self.channel_settings.lock().unwrap()
+    }
+
+    pub fn set_channel_settings(&mut self, settings: ChannelSettings) {
+        let mut settings = settings;
+        settings.balance = ((settings.balance * 10.0).round() / 10.0).clamp(-1.0, 1.0);
+        *self.channel_settings.lock().unwrap() = settings;
     }
+
+    pub fn adjust_balance(&mut self, delta: f32) {
+        let mut settings = self.channel_settings();
+        settings.balance += delta;
+        self.set_channel_settings(settings);
+    }
+
+    pub fn toggle_mono(&mut self) {
+        let mut settings = self.channel_settings();
+        settings.mono = !settings.mono;
+        self.set_channel_settings(settings);
+    }
+
+    pub fn toggle_swap_channels(&mut self) {
+        let mut settings = self.channel_settings();
+        settings.swap = !settings.swap;
+        self.set_channel_settings(settings);
+    }
+
+    /// Seeks to `duration` in absolute track time. Falls back to re-decoding
+    /// and skipping forward when the decoder rejects the seek.
+    pub fn seek(&mut self, duration: Duration) -> Result<(), String> {
+        if self.state == PlaybackState::Stopped {
+            return Ok(());
+        }
+        if self.is_seeking() {
+            // The sink is empty until the previous fallback finishes; start over.
+            return self.reopen_at(duration);
+        }
+
+        let start = self
+            .trim_window()
+            .map(|(start, _)| start)
+            .unwrap_or_default();
+        match self.sink.try_seek(duration.saturating_sub(start)) {
+            Ok(()) => Ok(()),
+            Err(seek_error) => self
+                .reopen_at(duration)
+                .map_err(|e| format!("Seek failed: {} ({})", seek_error, e)),
+        }
+    }
+}
+
+fn decode(file_bytes: Vec<u8>) -> Result<Decoder<std::io::Cursor<Vec<u8>>>, String> {
+    let byte_len = file_bytes.len() as u64;
+    let cursor = std::io::Cursor::new(file_bytes);
+
+    Decoder::builder()
+        .with_data(cursor)
+        .with_seekable(true)
+        .with_byte_len(byte_len)
+        .build()
+        .map_err(|e| format!("Failed to decode: {}", e))
 }
 
 impl Default for Player {
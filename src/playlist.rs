@@ -0,0 +1,871 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::collate::Collator;
+use crate::config::PlaylistPaths;
+use crate::scanner::Track;
+use crate::smart::SmartRules;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    M3u,
+    Pls,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "m3u" | "m3u8" => Some(Self::M3u),
+            "pls" => Some(Self::Pls),
+            "xspf" => Some(Self::Xspf),
+            _ => None,
+        }
+    }
+}
+
+/// An entry as written in a playlist file, before it is matched to the library.
+#[derive(Default)]
+pub struct ParsedEntry {
+    pub path: PathBuf,
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub duration: Option<u64>,
+}
+
+impl ParsedEntry {
+    fn describe(&self) -> String {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+            (None, Some(title)) => title.clone(),
+            _ => self.path.display().to_string(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Playlist {
+    pub name: String,
+    pub entries: Vec<PathBuf>,
+    /// Rules for smart playlists, whose entries are derived from the library.
+    pub smart: Option<SmartRules>,
+}
+
+/// On-disk form of a smart playlist.
+#[derive(Serialize, Deserialize)]
+struct SmartFile {
+    name: String,
+    #[serde(flatten)]
+    rules: SmartRules,
+}
+
+impl Playlist {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            entries: Vec::new(),
+            smart: None,
+        }
+    }
+
+    pub fn new_smart(name: &str, smart: SmartRules, tracks: &[Track], collator: &Collator) -> Self {
+        Self {
+            name: name.to_string(),
+            entries: smart.evaluate(tracks, collator),
+            smart: Some(smart),
+        }
+    }
+
+    /// Reads a smart playlist definition and evaluates it against the library.
+    pub fn load_smart(path: &Path, tracks: &[Track], collator: &Collator) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let file: SmartFile = serde_json::from_str(&content).map_err(io::Error::other)?;
+        file.rules
+            .validate()
+            .map_err(|e| io::Error::other(format!("{}: {}", file.name, e)))?;
+        Ok(Self::new_smart(&file.name, file.rules, tracks, collator))
+    }
+
+    /// Re-evaluates a smart playlist against the current library.
+    pub fn refresh(&mut self, tracks: &[Track], collator: &Collator) {
+        if let Some(smart) = &self.smart {
+            self.entries = smart.evaluate(tracks, collator);
+        }
+    }
+
+    /// Reads a playlist file in any supported format and matches its entries
+    /// against the library. Returns the entries that could not be matched.
+    pub fn load(path: &Path, tracks: &[Track]) -> io::Result<(Self, Vec<String>)> {
+        let format = PlaylistFormat::from_path(path)
+            .ok_or_else(|| io::Error::other("Unsupported playlist format"))?;
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Untitled")
+            .to_string();
+        let base = path.parent().unwrap_or(Path::new("."));
+        let content = read_text(path)?;
+
+        let parsed = match format {
+            PlaylistFormat::M3u => parse_m3u(&content, base),
+            PlaylistFormat::Pls => parse_pls(&content, base),
+            PlaylistFormat::Xspf => parse_xspf(&content, base),
+        };
+        let (entries, unresolved) = resolve_entries(parsed, tracks);
+
+        Ok((
+            Self {
+                name,
+                entries,
+                smart: None,
+            },
+            unresolved,
+        ))
+    }
+
+    /// Location of this playlist inside the tune data directory.
+    pub fn file_path(&self) -> Option<PathBuf> {
+        let extension = if self.smart.is_some() { "json" } else { "m3u8" };
+        playlists_dir().map(|dir| dir.join(format!("{}.{}", file_name(&self.name), extension)))
+    }
+
+    pub fn save(&self, tracks: &[Track], paths: PlaylistPaths) -> io::Result<()> {
+        let path = self
+            .file_path()
+            .ok_or_else(|| io::Error::other("No data directory"))?;
+        match &self.smart {
+            Some(rules) => {
+                let file = SmartFile {
+                    name: self.name.clone(),
+                    rules: rules.clone(),
+                };
+                let content = serde_json::to_string_pretty(&file).map_err(io::Error::other)?;
+                fs::write(path, content)
+            }
+            None => self.export(&path, tracks, paths),
+        }
+    }
+
+    /// Writes the playlist in the format matching `target`'s extension, M3U8 otherwise.
+    pub fn export(&self, target: &Path, tracks: &[Track], paths: PlaylistPaths) -> io::Result<()> {
+        let base = match paths {
+            PlaylistPaths::Absolute => None,
+            PlaylistPaths::Relative => target.parent(),
+        };
+        let content = match PlaylistFormat::from_path(target) {
+            Some(PlaylistFormat::Pls) => write_pls(&self.entries, tracks, base),
+            Some(PlaylistFormat::Xspf) => write_xspf(&self.name, &self.entries, tracks, base),
+            _ => write_m3u(&self.entries, tracks, base),
+        };
+        fs::write(target, content)
+    }
+
+    pub fn delete(&self) -> io::Result<()> {
+        match self.file_path() {
+            Some(path) if path.exists() => fs::remove_file(path),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn playlists_dir() -> Option<PathBuf> {
+    let mut path = dirs::data_dir()?;
+    path.push("tune");
+    path.push("playlists");
+    fs::create_dir_all(&path).ok();
+    Some(path)
+}
+
+pub fn is_playlist_file(path: &Path) -> bool {
+    PlaylistFormat::from_path(path).is_some()
+}
+
+/// Loads every playlist stored in the tune data directory, sorted by name.
+/// Also returns errors from smart playlist definitions that could not be read.
+pub fn load_playlists(tracks: &[Track], collator: &Collator) -> (Vec<Playlist>, Vec<String>) {
+    let Some(dir) = playlists_dir() else {
+        return (Vec::new(), Vec::new());
+    };
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return (Vec::new(), Vec::new());
+    };
+
+    let mut playlists = Vec::new();
+    let mut errors = Vec::new();
+    for path in read_dir.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.extension().is_some_and(|ext| ext == "json") {
+            match Playlist::load_smart(&path, tracks, collator) {
+                Ok(playlist) => playlists.push(playlist),
+                Err(e) => errors.push(e.to_string()),
+            }
+        } else if is_playlist_file(&path)
+            && let Ok((playlist, _)) = Playlist::load(&path, tracks)
+        {
+            playlists.push(playlist);
+        }
+    }
+
+    playlists.sort_by_key(|p| p.name.to_lowercase());
+    (playlists, errors)
+}
+
+/// Matches parsed entries to library tracks by path, falling back to a fuzzy
+/// artist/title/duration match for paths that no longer exist.
+pub fn resolve_entries(parsed: Vec<ParsedEntry>, tracks: &[Track]) -> (Vec<PathBuf>, Vec<String>) {
+    let library: HashSet<&PathBuf> = tracks.iter().map(|t| &t.path).collect();
+    let mut entries = Vec::with_capacity(parsed.len());
+    let mut unresolved = Vec::new();
+
+    for entry in parsed {
+        if library.contains(&entry.path) {
+            entries.push(entry.path);
+        } else if !entry.path.exists()
+            && let Some(track) = fuzzy_match(&entry, tracks)
+        {
+            entries.push(track.path.clone());
+        } else {
+            unresolved.push(entry.describe());
+            entries.push(entry.path);
+        }
+    }
+
+    (entries, unresolved)
+}
+
+fn fuzzy_match<'a>(entry: &ParsedEntry, tracks: &'a [Track]) -> Option<&'a Track> {
+    let title = entry
+        .title
+        .clone()
+        .or_else(|| {
+            entry
+                .path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .map(|s| s.to_string())
+        })
+        .map(|t| normalize_text(&t))
+        .filter(|t| !t.is_empty())?;
+    let artist = entry.artist.as_deref().map(normalize_text);
+
+    let mut best: Option<(u32, &Track)> = None;
+    for track in tracks {
+        let track_title = normalize_text(&track.title);
+        let mut score = if track_title == title {
+            4
+        } else if track_title.len() >= 3
+            && (title.contains(&track_title) || track_title.contains(&title))
+        {
+            2
+        } else {
+            continue;
+        };
+
+        if let Some(artist) = &artist {
+            let track_artist = normalize_text(&track.artist);
+            if track_artist == *artist {
+                score += 3;
+            } else if !track_artist.is_empty()
+                && (artist.contains(&track_artist) || track_artist.contains(artist.as_str()))
+            {
+                score += 1;
+            } else {
+                continue;
+            }
+        }
+
+        if let Some(duration) = entry.duration
+            && track.duration > 0
+        {
+            match duration.abs_diff(track.duration) {
+                0..=2 => score += 2,
+                3..=5 => score += 1,
+                6..=10 => {}
+                _ => continue,
+            }
+        }
+
+        if score >= 4 && best.is_none_or(|(best_score, _)| score > best_score) {
+            best = Some((score, track));
+        }
+    }
+
+    best.map(|(_, track)| track)
+}
+
+fn normalize_text(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn parse_m3u(content: &str, base: &Path) -> Vec<ParsedEntry> {
+    let mut entries = Vec::new();
+    let mut info: Option<(Option<u64>, Option<String>, Option<String>)> = None;
+
+    for line in content.lines() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            let (length, label) = extinf.split_once(',').unwrap_or((extinf, ""));
+            let duration = length
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .and_then(|d| u64::try_from(d).ok());
+            let (artist, title) = match label.split_once(" - ") {
+                Some((artist, title)) => (
+                    Some(artist.trim().to_string()),
+                    Some(title.trim().to_string()),
+                ),
+                None if !label.trim().is_empty() => (None, Some(label.trim().to_string())),
+                None => (None, None),
+            };
+            info = Some((duration, artist, title));
+            continue;
+        }
+
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let (duration, artist, title) = info.take().unwrap_or_default();
+        entries.push(ParsedEntry {
+            path: resolve_entry(line, base),
+            artist,
+            title,
+            duration,
+        });
+    }
+
+    entries
+}
+
+pub fn parse_pls(content: &str, base: &Path) -> Vec<ParsedEntry> {
+    let mut by_index: HashMap<u32, ParsedEntry> = HashMap::new();
+
+    for line in content.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+
+        let (field, index) =
+            key.split_at(key.find(|c: char| c.is_ascii_digit()).unwrap_or(key.len()));
+        let Ok(index) = index.parse::<u32>() else {
+            continue;
+        };
+        let entry = by_index.entry(index).or_default();
+
+        match field {
+            "file" => entry.path = resolve_entry(value, base),
+            "title" => {
+                let (artist, title) = match value.split_once(" - ") {
+                    Some((artist, title)) => {
+                        (Some(artist.trim().to_string()), title.trim().to_string())
+                    }
+                    None => (None, value.to_string()),
+                };
+                entry.artist = artist;
+                entry.title = Some(title);
+            }
+            "length" => {
+                entry.duration = value
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|d| u64::try_from(d).ok())
+            }
+            _ => {}
+        }
+    }
+
+    let mut indices: Vec<u32> = by_index.keys().copied().collect();
+    indices.sort_unstable();
+    indices
+        .into_iter()
+        .filter_map(|i| by_index.remove(&i))
+        .filter(|entry| !entry.path.as_os_str().is_empty())
+        .collect()
+}
+
+pub fn parse_xspf(content: &str, base: &Path) -> Vec<ParsedEntry> {
+    let mut entries = Vec::new();
+    let mut rest = content;
+
+    while let Some((block, after)) = xml_element(rest, "track") {
+        rest = after;
+
+        let Some(location) = xml_tag_text(block, "location") else {
+            continue;
+        };
+        // XSPF locations are URIs, so relative ones are percent-encoded too.
+        let location = if is_url(&location) {
+            location
+        } else {
+            percent_decode(&location)
+        };
+
+        entries.push(ParsedEntry {
+            path: resolve_entry(&location, base),
+            artist: xml_tag_text(block, "creator"),
+            title: xml_tag_text(block, "title"),
+            duration: xml_tag_text(block, "duration")
+                .and_then(|d| d.parse::<u64>().ok())
+                .map(|ms| ms / 1000),
+        });
+    }
+
+    entries
+}
+
+pub fn write_m3u(entries: &[PathBuf], tracks: &[Track], base: Option<&Path>) -> String {
+    let by_path = tracks_by_path(tracks);
+
+    let mut out = String::from("#EXTM3U\n");
+    for entry in entries {
+        if let Some(track) = by_path.get(entry) {
+            out.push_str(&format!(
+                "#EXTINF:{},{}\n",
+                track.duration,
+                track.display_name()
+            ));
+        }
+        out.push_str(&entry_location(entry, base));
+        out.push('\n');
+    }
+    out
+}
+
+pub fn write_pls(entries: &[PathBuf], tracks: &[Track], base: Option<&Path>) -> String {
+    let by_path = tracks_by_path(tracks);
+
+    let mut out = String::from("[playlist]\n");
+    for (i, entry) in entries.iter().enumerate() {
+        let n = i + 1;
+        out.push_str(&format!("File{}={}\n", n, entry_location(entry, base)));
+        if let Some(track) = by_path.get(entry) {
+            out.push_str(&format!("Title{}={}\n", n, track.display_name()));
+            out.push_str(&format!("Length{}={}\n", n, track.duration));
+        }
+    }
+    out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+    out
+}
+
+pub fn write_xspf(
+    name: &str,
+    entries: &[PathBuf],
+    tracks: &[Track],
+    base: Option<&Path>,
+) -> String {
+    let by_path = tracks_by_path(tracks);
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+    );
+    out.push_str(&format!(
+        "  <title>{}</title>\n  <trackList>\n",
+        xml_escape(name)
+    ));
+    for entry in entries {
+        let text = entry.to_string_lossy();
+        let location = match base {
+            // Stream URLs are kept as they are, like `resolve_entry` reads them.
+            _ if is_url(&text) => text.into_owned(),
+            Some(base) => percent_encode(&relative_path(entry, base).to_string_lossy()),
+            None => format!("file://{}", percent_encode(&text)),
+        };
+        out.push_str("    <track>\n");
+        out.push_str(&format!(
+            "      <location>{}</location>\n",
+            xml_escape(&location)
+        ));
+        if let Some(track) = by_path.get(entry) {
+            out.push_str(&format!(
+                "      <title>{}</title>\n",
+                xml_escape(&track.title)
+            ));
+            out.push_str(&format!(
+                "      <creator>{}</creator>\n",
+                xml_escape(&track.artist)
+            ));
+            out.push_str(&format!(
+                "      <duration>{}</duration>\n",
+                track.duration * 1000
+            ));
+        }
+        out.push_str("    </track>\n");
+    }
+    out.push_str("  </trackList>\n</playlist>\n");
+    out
+}
+
+fn tracks_by_path(tracks: &[Track]) -> HashMap<&PathBuf, &Track> {
+    tracks.iter().map(|t| (&t.path, t)).collect()
+}
+
+fn entry_location(entry: &Path, base: Option<&Path>) -> String {
+    match base {
+        Some(base) => relative_path(entry, base),
+        None => entry.to_path_buf(),
+    }
+    .to_string_lossy()
+    .into_owned()
+}
+
+/// Resolves a playlist entry (plain path or percent-encoded `file://` URL)
+/// against `base`. Other URLs, such as web radio streams, are kept verbatim.
+pub fn resolve_entry(entry: &str, base: &Path) -> PathBuf {
+    let decoded;
+    let entry = match entry.strip_prefix("file://") {
+        Some(uri) => {
+            decoded = percent_decode(uri.strip_prefix("localhost").unwrap_or(uri));
+            decoded.as_str()
+        }
+        None if is_url(entry) => return PathBuf::from(entry),
+        None => entry,
+    };
+
+    let path = PathBuf::from(entry.replace('\\', "/"));
+    if path.is_absolute() {
+        path
+    } else {
+        normalize_path(&base.join(path))
+    }
+}
+
+/// Reads a text file as UTF-8, falling back to Latin-1 for legacy `.m3u` files.
+pub fn read_text(path: &Path) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    Ok(match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => e.into_bytes().iter().map(|&b| b as char).collect(),
+    })
+}
+
+fn xml_tag_text(block: &str, tag: &str) -> Option<String> {
+    xml_element(block, tag).map(|(content, _)| xml_unescape(content.trim()))
+}
+
+/// Finds the next `tag` element, whose opening tag may carry attributes or
+/// whitespace. Returns its content and the text after its closing tag.
+fn xml_element<'a>(text: &'a str, tag: &str) -> Option<(&'a str, &'a str)> {
+    let start = find_tag(text, &format!("<{}", tag))?;
+    let open_end = text[start..].find('>')? + start;
+    if text[..open_end].ends_with('/') {
+        return Some(("", &text[open_end + 1..]));
+    }
+    let content = &text[open_end + 1..];
+    let end = find_tag(content, &format!("</{}", tag))?;
+    let after = content[end..]
+        .find('>')
+        .map_or("", |i| &content[end + i + 1..]);
+    Some((&content[..end], after))
+}
+
+/// Position of `prefix` where it is followed by the end of a tag name,
+/// so that `<track` does not match `<trackList>`.
+fn find_tag(text: &str, prefix: &str) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(found) = text[offset..].find(prefix) {
+        let start = offset + found;
+        let next = text[start + prefix.len()..].chars().next();
+        if next.is_some_and(|c| c == '>' || c == '/' || c.is_whitespace()) {
+            return Some(start);
+        }
+        offset = start + prefix.len();
+    }
+    None
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn xml_unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let Some(end) = after.find(';') else {
+            break;
+        };
+        let entity = &after[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse::<u32>()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &after[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn is_url(entry: &str) -> bool {
+    entry.contains("://")
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            out.push(byte);
+            i += 3;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path_parts: Vec<Component> = path.components().collect();
+    let base_parts: Vec<Component> = base.components().collect();
+
+    let common = path_parts
+        .iter()
+        .zip(&base_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    if common == 0 {
+        return path.to_path_buf();
+    }
+
+    let mut out = PathBuf::new();
+    for _ in common..base_parts.len() {
+        out.push("..");
+    }
+    for part in &path_parts[common..] {
+        out.push(part);
+    }
+    out
+}
+
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATHS: [&str; 3] = [
+        "/music/Café Tacvba/Re/01 La Ingrata.mp3",
+        "/music/Nina Simone/Feeling Good (live).flac",
+        "/music/100% Hits/Song #1 & more.ogg",
+    ];
+
+    fn library() -> Vec<Track> {
+        PATHS
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let mut track = Track::from_path(PathBuf::from(path));
+                track.artist = format!("Artist {}", i);
+                track.title = format!("Title <{}> & co", i);
+                track.duration = 100 + i as u64;
+                track
+            })
+            .collect()
+    }
+
+    fn entries() -> Vec<PathBuf> {
+        PATHS.iter().map(PathBuf::from).collect()
+    }
+
+    fn assert_round_trip(parsed: Vec<ParsedEntry>, tracks: &[Track]) {
+        assert_eq!(parsed.len(), tracks.len());
+        for (entry, track) in parsed.iter().zip(tracks) {
+            assert_eq!(entry.path, track.path);
+            assert_eq!(entry.artist.as_deref(), Some(track.artist.as_str()));
+            assert_eq!(entry.title.as_deref(), Some(track.title.as_str()));
+            assert_eq!(entry.duration, Some(track.duration));
+        }
+    }
+
+    #[test]
+    fn m3u_round_trips_absolute_and_relative_paths() {
+        let tracks = library();
+        let base = Path::new("/music");
+        let absolute = write_m3u(&entries(), &tracks, None);
+        assert_round_trip(parse_m3u(&absolute, Path::new("/elsewhere")), &tracks);
+        let relative = write_m3u(&entries(), &tracks, Some(base));
+        assert!(relative.contains("\nNina Simone/Feeling Good (live).flac\n"));
+        assert_round_trip(parse_m3u(&relative, base), &tracks);
+    }
+
+    #[test]
+    fn pls_round_trips_absolute_and_relative_paths() {
+        let tracks = library();
+        let base = Path::new("/music");
+        let absolute = write_pls(&entries(), &tracks, None);
+        assert_round_trip(parse_pls(&absolute, Path::new("/elsewhere")), &tracks);
+        let relative = write_pls(&entries(), &tracks, Some(base));
+        assert_round_trip(parse_pls(&relative, base), &tracks);
+    }
+
+    #[test]
+    fn xspf_round_trips_absolute_and_relative_paths() {
+        let tracks = library();
+        let base = Path::new("/music");
+        let absolute = write_xspf("Mix & Match", &entries(), &tracks, None);
+        assert!(absolute.contains("file:///music/Caf%C3%A9%20Tacvba/"));
+        assert_round_trip(parse_xspf(&absolute, Path::new("/elsewhere")), &tracks);
+        let relative = write_xspf("Mix & Match", &entries(), &tracks, Some(base));
+        assert_round_trip(parse_xspf(&relative, base), &tracks);
+    }
+
+    #[test]
+    fn xspf_tracks_may_carry_attributes_and_whitespace() {
+        let content = r#"<?xml version="1.0"?>
+<playlist version="1" xmlns="http://xspf.org/ns/0/">
+  <trackList >
+    <track xml:id="one">
+      <location >file:///music/a%20b.mp3</location>
+      <title>A &amp; B</title>
+    </track>
+    <track
+      xml:id="two"><location>c.mp3</location></track >
+    <track/>
+  </trackList>
+</playlist>"#;
+        let parsed = parse_xspf(content, Path::new("/base"));
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].path, PathBuf::from("/music/a b.mp3"));
+        assert_eq!(parsed[0].title.as_deref(), Some("A & B"));
+        assert_eq!(parsed[1].path, PathBuf::from("/base/c.mp3"));
+    }
+
+    #[test]
+    fn stream_urls_are_written_unchanged() {
+        let stream = PathBuf::from("http://radio.example/live?id=5&q=1");
+        let entries = vec![PathBuf::from("/music/a b.mp3"), stream.clone()];
+        for base in [None, Some(Path::new("/music"))] {
+            let content = write_xspf("Radio", &entries, &[], base);
+            assert!(
+                content.contains("<location>http://radio.example/live?id=5&amp;q=1</location>")
+            );
+            let parsed = parse_xspf(&content, Path::new("/music"));
+            assert_eq!(parsed[0].path, entries[0]);
+            assert_eq!(parsed[1].path, stream);
+        }
+        for base in [None, Some(Path::new("/music"))] {
+            let m3u = write_m3u(&entries, &[], base);
+            assert_eq!(parse_m3u(&m3u, Path::new("/music"))[1].path, stream);
+            let pls = write_pls(&entries, &[], base);
+            assert_eq!(parse_pls(&pls, Path::new("/music"))[1].path, stream);
+        }
+    }
+
+    #[test]
+    fn file_uris_are_percent_decoded() {
+        let base = Path::new("/base");
+        assert_eq!(
+            resolve_entry("file:///music/My%20Song%20%C3%A9.mp3", base),
+            PathBuf::from("/music/My Song é.mp3")
+        );
+        assert_eq!(
+            resolve_entry("file://localhost/music/a%20b.mp3", base),
+            PathBuf::from("/music/a b.mp3")
+        );
+        // Plain paths are taken literally, percent signs included.
+        assert_eq!(
+            resolve_entry("/music/100%20.mp3", base),
+            PathBuf::from("/music/100%20.mp3")
+        );
+        assert_eq!(
+            resolve_entry("http://radio.example/stream%20one", base),
+            PathBuf::from("http://radio.example/stream%20one")
+        );
+
+        let m3u = "#EXTM3U\nfile:///music/Caf%C3%A9/a%20b.mp3\n";
+        assert_eq!(
+            parse_m3u(m3u, base)[0].path,
+            PathBuf::from("/music/Café/a b.mp3")
+        );
+        let pls = "[playlist]\nFile1=file:///music/a%20b.mp3\nNumberOfEntries=1\n";
+        assert_eq!(
+            parse_pls(pls, base)[0].path,
+            PathBuf::from("/music/a b.mp3")
+        );
+    }
+}
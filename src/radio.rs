@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use rand::seq::SliceRandom;
+
+use crate::history::History;
+use crate::scanner::Track;
+
+/// Years apart that still count as the same era.
+const YEAR_RANGE: u32 = 5;
+
+/// Plays this close together in the history count as listened together.
+const CO_PLAY_DISTANCE: usize = 3;
+
+/// Cap on the co-occurrence score so history cannot drown out the tags.
+const MAX_CO_PLAY_SCORE: u32 = 4;
+
+/// Picks a library track similar to `seed` for radio mode, skipping the
+/// tracks in `exclude`. Falls back to any allowed track when nothing is similar.
+pub fn pick_similar(
+    seed: usize,
+    tracks: &[Track],
+    history: &History,
+    exclude: &HashSet<&Path>,
+) -> Option<usize> {
+    let seed_track = &tracks[seed];
+    let co_plays = co_plays(seed_track.path.as_path(), history);
+
+    let candidates: Vec<(usize, u32)> = tracks
+        .iter()
+        .enumerate()
+        .filter(|&(index, track)| index != seed && !exclude.contains(track.path.as_path()))
+        .map(|(index, track)| (index, similarity(seed_track, track, &co_plays)))
+        .collect();
+
+    let mut rng = rand::thread_rng();
+    let similar: Vec<&(usize, u32)> = candidates.iter().filter(|(_, score)| *score > 0).collect();
+    if similar.is_empty() {
+        return candidates.choose(&mut rng).map(|&(index, _)| index);
+    }
+    // Squaring the score favours close matches while still leaving some variety.
+    similar
+        .choose_weighted(&mut rng, |(_, score)| score * score)
+        .ok()
+        .map(|&&(index, _)| index)
+}
+
+fn similarity(seed: &Track, track: &Track, co_plays: &HashMap<&Path, u32>) -> u32 {
+    let mut score = 0;
+    if same_text(&seed.artist, &track.artist) {
+        score += 3;
+    }
+    if let (Some(a), Some(b)) = (&seed.album_artist, &track.album_artist)
+        && same_text(a, b)
+    {
+        score += 2;
+    }
+    if let (Some(a), Some(b)) = (&seed.genre, &track.genre)
+        && same_text(a, b)
+    {
+        score += 2;
+    }
+    if let (Some(a), Some(b)) = (seed.year, track.year)
+        && a.abs_diff(b) <= YEAR_RANGE
+    {
+        score += 1;
+    }
+    score
+        + co_plays
+            .get(track.path.as_path())
+            .map_or(0, |&count| count.min(MAX_CO_PLAY_SCORE))
+}
+
+fn same_text(a: &str, b: &str) -> bool {
+    let a = a.trim();
+    !a.is_empty() && a.eq_ignore_ascii_case(b.trim())
+}
+
+/// How often each track was played close to `seed` without being skipped.
+fn co_plays<'a>(seed: &Path, history: &'a History) -> HashMap<&'a Path, u32> {
+    let records = &history.records;
+    let mut counts = HashMap::new();
+    for (i, record) in records.iter().enumerate() {
+        if record.path != seed || record.skipped {
+            continue;
+        }
+        let start = i.saturating_sub(CO_PLAY_DISTANCE);
+        let end = (i + CO_PLAY_DISTANCE + 1).min(records.len());
+        for other in &records[start..end] {
+            if other.path != seed && !other.skipped {
+                *counts.entry(other.path.as_path()).or_default() += 1;
+            }
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::history::PlayRecord;
+
+    fn track(name: &str, artist: &str, genre: Option<&str>, year: Option<u32>) -> Track {
+        let mut track = Track::from_path(PathBuf::from(format!("/m/{}.mp3", name)));
+        track.artist = artist.to_string();
+        track.genre = genre.map(str::to_string);
+        track.year = year;
+        track
+    }
+
+    fn play(name: &str, skipped: bool) -> PlayRecord {
+        PlayRecord {
+            path: PathBuf::from(format!("/m/{}.mp3", name)),
+            artist: String::new(),
+            title: name.to_string(),
+            album: None,
+            started_at: 0,
+            listened_secs: 60,
+            duration_secs: 180,
+            skipped,
+        }
+    }
+
+    fn history(plays: &[(&str, bool)]) -> History {
+        History {
+            records: plays
+                .iter()
+                .map(|&(name, skipped)| play(name, skipped))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn similarity_adds_up_shared_tags() {
+        let seed = track("seed", "Miles Davis", Some("Jazz"), Some(1959));
+        let none = HashMap::new();
+        let score = |track: &Track| similarity(&seed, track, &none);
+
+        assert_eq!(score(&track("a", " miles davis ", None, None)), 3);
+        assert_eq!(score(&track("b", "Coltrane", Some("JAZZ"), Some(1964))), 3);
+        assert_eq!(score(&track("c", "Coltrane", Some("Jazz"), Some(1965))), 2);
+        assert_eq!(
+            score(&track("d", "Miles Davis", Some("Jazz"), Some(1954))),
+            6
+        );
+        assert_eq!(score(&track("e", "Bach", Some("Baroque"), None)), 0);
+
+        let mut compilation = track("f", "Bill Evans", None, None);
+        compilation.album_artist = Some("Miles Davis".to_string());
+        let mut seed = seed.clone();
+        seed.album_artist = Some("miles davis".to_string());
+        assert_eq!(similarity(&seed, &compilation, &none), 2);
+
+        // Empty tags never match each other.
+        let blank = track("g", "", Some(""), None);
+        assert_eq!(
+            similarity(&track("h", "", Some(""), None), &blank, &none),
+            0
+        );
+    }
+
+    #[test]
+    fn co_plays_count_unskipped_neighbours() {
+        let history = history(&[
+            ("far", false),
+            ("a", false),
+            ("b", true),
+            ("c", false),
+            ("seed", false),
+            ("d", false),
+            ("a", false),
+            ("e", false),
+            ("seed", true),
+            ("f", false),
+            ("seed", false),
+        ]);
+        let counts = co_plays(Path::new("/m/seed.mp3"), &history);
+        let count = |name: &str| {
+            counts
+                .get(PathBuf::from(format!("/m/{}.mp3", name)).as_path())
+                .copied()
+        };
+
+        // Every play within three of an unskipped seed play counts.
+        assert_eq!(count("a"), Some(2));
+        assert_eq!(count("c"), Some(1));
+        assert_eq!(count("d"), Some(1));
+        assert_eq!(count("e"), Some(2));
+        assert_eq!(count("f"), Some(1));
+        // Skipped neighbours, plays too far away and the seed itself are left out.
+        assert_eq!(count("b"), None);
+        assert_eq!(count("far"), None);
+        assert_eq!(count("seed"), None);
+    }
+
+    #[test]
+    fn co_plays_add_to_similarity_up_to_a_cap() {
+        let seed = track("seed", "Miles Davis", None, None);
+        let other = track("other", "Bach", None, None);
+        let mut plays = Vec::new();
+        for _ in 0..10 {
+            plays.extend([("seed", false), ("other", false)]);
+        }
+        let history = history(&plays);
+        let counts = co_plays(seed.path.as_path(), &history);
+        assert!(counts[other.path.as_path()] > MAX_CO_PLAY_SCORE);
+        assert_eq!(similarity(&seed, &other, &counts), MAX_CO_PLAY_SCORE);
+    }
+
+    #[test]
+    fn picks_only_similar_tracks_outside_the_exclusions() {
+        let tracks = vec![
+            track("seed", "Miles Davis", None, None),
+            track("same", "Miles Davis", None, None),
+            track("recent", "Miles Davis", None, None),
+            track("other", "Bach", None, None),
+        ];
+        let history = history(&[]);
+        let exclude = HashSet::from([tracks[2].path.as_path()]);
+        for _ in 0..50 {
+            assert_eq!(pick_similar(0, &tracks, &history, &exclude), Some(1));
+        }
+
+        // With nothing similar left, any allowed track will do.
+        let exclude = HashSet::from([tracks[1].path.as_path(), tracks[2].path.as_path()]);
+        assert_eq!(pick_similar(0, &tracks, &history, &exclude), Some(3));
+        let exclude: HashSet<&Path> = tracks.iter().map(|t| t.path.as_path()).collect();
+        assert_eq!(pick_similar(0, &tracks, &history, &exclude), None);
+    }
+}
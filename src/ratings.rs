@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lofty::config::WriteOptions;
+use lofty::id3::v2::PopularimeterFrame;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemValue, TagItem, TagType};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_STARS: u8 = 5;
+
+/// POPM ratings written for 1 to 5 stars, as used by most players.
+const POPM_VALUES: [u8; 6] = [0, 1, 64, 128, 196, 255];
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rating {
+    pub stars: u8,
+    pub favorite: bool,
+}
+
+/// Ratings and favorites keyed by track path, stored in the tune data directory.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Ratings(HashMap<PathBuf, Rating>);
+
+impl Ratings {
+    pub fn get(&self, path: &Path) -> Rating {
+        self.0.get(path).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, path: &Path, rating: Rating) {
+        if rating == Rating::default() {
+            self.0.remove(path);
+        } else {
+            self.0.insert(path.to_path_buf(), rating);
+        }
+    }
+
+    pub fn load() -> Self {
+        if let Some(path) = ratings_path()
+            && let Ok(content) = fs::read_to_string(&path)
+            && let Ok(ratings) = serde_json::from_str(&content)
+        {
+            return ratings;
+        }
+        Self::default()
+    }
+
+    pub fn save(&self) {
+        if let Some(path) = ratings_path()
+            && let Ok(content) = serde_json::to_string_pretty(self)
+        {
+            fs::write(path, content).ok();
+        }
+    }
+}
+
+fn ratings_path() -> Option<PathBuf> {
+    let mut path = dirs::data_dir()?;
+    path.push("tune");
+    fs::create_dir_all(&path).ok();
+    path.push("ratings.json");
+    Some(path)
+}
+
+/// Writes the star rating into the file's own tag: POPM for ID3v2,
+/// FMPS_RATING and RATING for Vorbis comments, IRTD for RIFF INFO.
+pub fn write_rating_tag(path: &Path, stars: u8) -> Result<(), String> {
+    let mut tagged_file = Probe::open(path)
+        .and_then(|p| p.read())
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+
+    let tag_type = tagged_file.primary_tag_type();
+    if tagged_file.tag(tag_type).is_none() {
+        tagged_file.insert_tag(lofty::tag::Tag::new(tag_type));
+    }
+    let Some(tag) = tagged_file.tag_mut(tag_type) else {
+        return Err("No tag to write".to_string());
+    };
+
+    let stars = stars.min(MAX_STARS);
+    match tag_type {
+        TagType::Id3v2 => {
+            let frame = PopularimeterFrame::new(String::new(), POPM_VALUES[stars as usize], 0);
+            let bytes = frame
+                .as_bytes()
+                .map_err(|e| format!("Failed to encode rating: {}", e))?;
+            tag.insert(TagItem::new(
+                ItemKey::Popularimeter,
+                ItemValue::Binary(bytes),
+            ));
+        }
+        TagType::VorbisComments => {
+            tag.insert_text(
+                ItemKey::Unknown("FMPS_RATING".to_string()),
+                format!("{:.1}", stars as f32 / MAX_STARS as f32),
+            );
+            tag.insert_text(ItemKey::Popularimeter, (stars as u32 * 20).to_string());
+        }
+        _ => {
+            tag.insert_text(ItemKey::Popularimeter, (stars as u32 * 20).to_string());
+        }
+    }
+
+    tag.save_to_path(path, WriteOptions::default())
+        .map_err(|e| format!("Failed to write rating tag: {}", e))
+}
@@ -4,15 +4,35 @@ use lofty::tag::ItemKey;
 use std::path::PathBuf;
 use walkdir::WalkDir;
 
+use crate::collate::Collator;
+use crate::columns::{Column, SortKey, SortSpec};
 use crate::config::{Config, SUPPORTED_EXTENSIONS};
+use crate::playlist::is_playlist_file;
 
 #[derive(Clone, Debug)]
 pub struct Track {
     pub path: PathBuf,
     pub title: String,
     pub artist: String,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    /// ARTISTSORT, ALBUMARTISTSORT, ALBUMSORT and TITLESORT tags.
+    pub artist_sort: Option<String>,
+    pub album_artist_sort: Option<String>,
+    pub album_sort: Option<String>,
+    pub title_sort: Option<String>,
+    pub track_number: Option<u32>,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
     pub duration: u64,
+    /// Audio bitrate in kbps.
+    pub bitrate: Option<u32>,
     pub lyrics: Option<String>,
+    /// Star rating from 0 to 5, loaded from tune's ratings store.
+    pub rating: u8,
+    pub favorite: bool,
+    /// Completed plays from the listening history.
+    pub plays: u32,
 }
 
 impl Track {
@@ -25,11 +45,22 @@ impl Track {
 
         let mut title = filename.clone();
         let mut artist = String::from("Unknown Artist");
+        let mut album = None;
+        let mut album_artist = None;
+        let mut artist_sort = None;
+        let mut album_artist_sort = None;
+        let mut album_sort = None;
+        let mut title_sort = None;
+        let mut track_number = None;
+        let mut genre = None;
+        let mut year = None;
         let mut duration = 0;
+        let mut bitrate = None;
         let mut lyrics = None;
 
         if let Ok(tagged_file) = Probe::open(&path).and_then(|p| p.read()) {
             duration = tagged_file.properties().duration().as_secs();
+            bitrate = tagged_file.properties().audio_bitrate();
 
             if let Some(tag) = tagged_file.primary_tag() {
                 if let Some(t) = tag.title() {
@@ -38,6 +69,21 @@ impl Track {
                 if let Some(a) = tag.artist() {
                     artist = a.to_string();
                 }
+                album = tag.album().map(|a| a.to_string());
+                album_artist = tag.get_string(&ItemKey::AlbumArtist).map(|a| a.to_string());
+                let sort_tag = |key| {
+                    tag.get_string(&key)
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(|s| s.to_string())
+                };
+                artist_sort = sort_tag(ItemKey::TrackArtistSortOrder);
+                album_artist_sort = sort_tag(ItemKey::AlbumArtistSortOrder);
+                album_sort = sort_tag(ItemKey::AlbumTitleSortOrder);
+                title_sort = sort_tag(ItemKey::TrackTitleSortOrder);
+                track_number = tag.track();
+                genre = tag.genre().map(|g| g.to_string());
+                year = tag.year();
                 lyrics = tag.get_string(&ItemKey::Lyrics).map(|s| s.to_string());
             }
         }
@@ -46,8 +92,21 @@ impl Track {
             path: path.to_path_buf(),
             title,
             artist,
+            album,
+            album_artist,
+            artist_sort,
+            album_artist_sort,
+            album_sort,
+            title_sort,
+            track_number,
+            genre,
+            year,
             duration,
+            bitrate,
             lyrics,
+            rating: 0,
+            favorite: false,
+            plays: 0,
         }
     }
 
@@ -60,8 +119,15 @@ impl Track {
     }
 }
 
-pub fn scan_music_directory(config: &Config) -> Vec<Track> {
+pub struct ScanResult {
+    pub tracks: Vec<Track>,
+    /// Playlist files (`.m3u`, `.m3u8`, `.pls`, `.xspf`) found alongside the music.
+    pub playlist_files: Vec<PathBuf>,
+}
+
+pub fn scan_music_directory(config: &Config) -> ScanResult {
     let mut tracks = Vec::new();
+    let mut playlist_files = Vec::new();
 
     for entry in WalkDir::new(&config.music_dir)
         .follow_links(true)
@@ -74,6 +140,11 @@ pub fn scan_music_directory(config: &Config) -> Vec<Track> {
             continue;
         }
 
+        if is_playlist_file(path) {
+            playlist_files.push(path.to_path_buf());
+            continue;
+        }
+
         if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
             let ext_lower = ext.to_lowercase();
             if SUPPORTED_EXTENSIONS.contains(&ext_lower.as_str()) {
@@ -82,12 +153,21 @@ pub fn scan_music_directory(config: &Config) -> Vec<Track> {
         }
     }
 
-    tracks.sort_by(|a, b| {
-        a.artist
-            .to_lowercase()
-            .cmp(&b.artist.to_lowercase())
-            .then_with(|| a.title.to_lowercase().cmp(&b.title.to_lowercase()))
-    });
+    let collator = Collator::new(config);
+    let order = SortSpec(vec![
+        SortKey {
+            column: Column::Artist,
+            descending: false,
+        },
+        SortKey {
+            column: Column::Title,
+            descending: false,
+        },
+    ]);
+    tracks.sort_by(|a, b| order.compare(a, b, &collator));
 
-    tracks
+    ScanResult {
+        tracks,
+        playlist_files,
+    }
 }
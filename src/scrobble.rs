@@ -0,0 +1,78 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::scanner::Track;
+
+/// Tracks this short are never scrobbled.
+pub const MIN_TRACK_SECS: u64 = 30;
+
+/// Listening this long qualifies a play even when it is under half the track.
+const QUALIFYING_SECS: u64 = 240;
+
+const LOG_HEADER: &str = "#AUDIOSCROBBLER/1.1\n#TZ/UTC\n";
+
+/// The standard scrobbling rule: the track is longer than 30 seconds and
+/// was played for at least half its length or four minutes.
+pub fn qualifies(listened_secs: u64, duration_secs: u64) -> bool {
+    duration_secs > MIN_TRACK_SECS
+        && (listened_secs * 2 >= duration_secs || listened_secs >= QUALIFYING_SECS)
+}
+
+/// Portable-player `.scrobbler.log` that offline uploaders read and submit.
+pub struct ScrobbleLog {
+    pub path: PathBuf,
+}
+
+impl ScrobbleLog {
+    /// Uses `path` from the config, or `.scrobbler.log` in the tune data directory.
+    pub fn new(path: Option<PathBuf>) -> Option<Self> {
+        let path = path.or_else(|| {
+            let mut path = dirs::data_dir()?;
+            path.push("tune");
+            fs::create_dir_all(&path).ok();
+            path.push(".scrobbler.log");
+            Some(path)
+        })?;
+        Some(Self { path })
+    }
+
+    /// Appends a play, rated `L` when it qualified as listened and `S` when skipped.
+    pub fn append(&self, track: &Track, started_at: u64, listened: bool) -> Result<(), String> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open scrobbler log: {}", e))?;
+
+        let is_new = file.metadata().map(|m| m.len() == 0).unwrap_or(true);
+        let header = if is_new {
+            format!("{}#CLIENT/tune {}\n", LOG_HEADER, env!("CARGO_PKG_VERSION"))
+        } else {
+            String::new()
+        };
+
+        let line = [
+            field(&track.artist),
+            field(track.album.as_deref().unwrap_or_default()),
+            field(&track.title),
+            track
+                .track_number
+                .map(|n| n.to_string())
+                .unwrap_or_default(),
+            track.duration.to_string(),
+            if listened { "L" } else { "S" }.to_string(),
+            started_at.to_string(),
+            String::new(),
+        ]
+        .join("\t");
+
+        writeln!(file, "{}{}", header, line)
+            .map_err(|e| format!("Failed to write scrobbler log: {}", e))
+    }
+}
+
+/// Fields are tab separated, so tabs and line breaks inside them become spaces.
+fn field(text: &str) -> String {
+    text.replace(['\t', '\n', '\r'], " ")
+}
@@ -0,0 +1,115 @@
+use crate::scanner::Track;
+
+/// How far a fuzzy match may spread, relative to the term length.
+const MAX_SPREAD: usize = 3;
+
+/// Library tracks matching a search query, in library order.
+pub struct TrackFilter {
+    pub query: String,
+    pub matches: Vec<usize>,
+}
+
+impl TrackFilter {
+    pub fn new(query: &str, tracks: &[Track]) -> Self {
+        let terms = terms(query);
+        let matches = tracks
+            .iter()
+            .enumerate()
+            .filter(|(_, track)| matches_track(&terms, track))
+            .map(|(index, _)| index)
+            .collect();
+
+        Self {
+            query: query.to_string(),
+            matches,
+        }
+    }
+}
+
+fn terms(query: &str) -> Vec<Vec<char>> {
+    query.split_whitespace().map(lowercase_chars).collect()
+}
+
+fn lowercase_chars(text: &str) -> Vec<char> {
+    text.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+/// Every term has to match the title, artist or path. `rating:N` keeps tracks
+/// rated N stars or more and `is:fav` keeps favorites.
+fn matches_track(terms: &[Vec<char>], track: &Track) -> bool {
+    let fields = [
+        lowercase_chars(&track.title),
+        lowercase_chars(&track.artist),
+        lowercase_chars(&track.path.to_string_lossy()),
+    ];
+    terms.iter().all(|term| {
+        let text: String = term.iter().collect();
+        if let Some(stars) = text
+            .strip_prefix("rating:")
+            .and_then(|n| n.parse::<u8>().ok())
+        {
+            return track.rating >= stars;
+        }
+        if text == "is:fav" {
+            return track.favorite;
+        }
+        fields
+            .iter()
+            .any(|field| term_positions(term, field).is_some())
+    })
+}
+
+/// Char positions in `text` matched by any term of `query`, for highlighting.
+pub fn highlight_positions(query: &str, text: &str) -> Vec<usize> {
+    let text = lowercase_chars(text);
+    let mut positions: Vec<usize> = terms(query)
+        .iter()
+        .filter(|term| !term.contains(&':'))
+        .filter_map(|term| term_positions(term, &text))
+        .flatten()
+        .collect();
+    positions.sort_unstable();
+    positions.dedup();
+    positions
+}
+
+/// Matches `term` as a substring if possible, otherwise as a compact subsequence.
+fn term_positions(term: &[char], text: &[char]) -> Option<Vec<usize>> {
+    if term.is_empty() || term.len() > text.len() {
+        return None;
+    }
+
+    if let Some(start) = text.windows(term.len()).position(|w| w == term) {
+        return Some((start..start + term.len()).collect());
+    }
+
+    let mut best: Option<Vec<usize>> = None;
+    for start in (0..text.len()).filter(|&i| text[i] == term[0]) {
+        let mut positions = vec![start];
+        let mut next = start + 1;
+        for &c in &term[1..] {
+            match text[next..].iter().position(|&t| t == c) {
+                Some(offset) => {
+                    positions.push(next + offset);
+                    next += offset + 1;
+                }
+                None => break,
+            }
+        }
+        if positions.len() < term.len() {
+            break;
+        }
+
+        let spread = next - start;
+        if best
+            .as_ref()
+            .is_none_or(|b| spread < b[b.len() - 1] + 1 - b[0])
+        {
+            best = Some(positions);
+        }
+    }
+
+    best.filter(|b| b[b.len() - 1] + 1 - b[0] <= term.len() * MAX_SPREAD)
+}
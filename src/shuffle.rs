@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::history::now_secs;
+use crate::library::Library;
+use crate::scanner::Track;
+
+/// Days after which a played track counts as fresh again in weighted shuffle.
+const RECENCY_DAYS: f64 = 30.0;
+
+/// Weight left to a track played just now, relative to an unplayed one.
+const MIN_RECENCY_WEIGHT: f64 = 0.1;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShuffleMode {
+    /// Every track in uniformly random order.
+    #[default]
+    Tracks,
+    /// Albums in random order, each played in track order.
+    Albums,
+    /// Random order that keeps tracks by the same artist apart.
+    ArtistSpread,
+    /// Higher rated and less recently played tracks come up sooner.
+    Weighted,
+}
+
+impl ShuffleMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ShuffleMode::Tracks => "Tracks",
+            ShuffleMode::Albums => "Albums",
+            ShuffleMode::ArtistSpread => "Artist spread",
+            ShuffleMode::Weighted => "Weighted",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ShuffleMode::Tracks => ShuffleMode::Albums,
+            ShuffleMode::Albums => ShuffleMode::ArtistSpread,
+            ShuffleMode::ArtistSpread => ShuffleMode::Weighted,
+            ShuffleMode::Weighted => ShuffleMode::Tracks,
+        }
+    }
+}
+
+/// Builds a shuffled play order over every library index. `last_played`
+/// maps track paths to the Unix time they were last played.
+pub fn shuffled_order(
+    mode: ShuffleMode,
+    tracks: &[Track],
+    library: &Library,
+    last_played: &HashMap<&Path, u64>,
+) -> Vec<usize> {
+    let mut rng = rand::thread_rng();
+    match mode {
+        ShuffleMode::Tracks => {
+            let mut order: Vec<usize> = (0..tracks.len()).collect();
+            order.shuffle(&mut rng);
+            order
+        }
+        ShuffleMode::Albums => {
+            let mut albums: Vec<&Vec<usize>> = library
+                .artists
+                .iter()
+                .flat_map(|artist| artist.albums.iter().map(|album| &album.tracks))
+                .collect();
+            albums.shuffle(&mut rng);
+            albums.into_iter().flatten().copied().collect()
+        }
+        ShuffleMode::ArtistSpread => {
+            let mut groups: Vec<Vec<usize>> = library.artists.iter().map(|a| a.tracks()).collect();
+            for group in &mut groups {
+                group.shuffle(&mut rng);
+            }
+            spread(groups, &mut rng)
+        }
+        ShuffleMode::Weighted => weighted(tracks, last_played, now_secs(), &mut rng),
+    }
+}
+
+/// Orders tracks so that heavier ones tend to come first, using weighted
+/// sampling without replacement (Efraimidis-Spirakis).
+fn weighted(
+    tracks: &[Track],
+    last_played: &HashMap<&Path, u64>,
+    now: u64,
+    rng: &mut impl Rng,
+) -> Vec<usize> {
+    let mut keyed: Vec<(f64, usize)> = tracks
+        .iter()
+        .enumerate()
+        .map(|(index, track)| {
+            let weight =
+                rating_weight(track) * recency_weight(last_played.get(track.path.as_path()), now);
+            let key = rng.r#gen::<f64>().powf(1.0 / weight);
+            (key, index)
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    keyed.into_iter().map(|(_, index)| index).collect()
+}
+
+/// Interleaves the groups at random so that two tracks from the same group
+/// only follow each other once no other group has tracks left.
+fn spread(mut groups: Vec<Vec<usize>>, rng: &mut impl Rng) -> Vec<usize> {
+    let total: usize = groups.iter().map(Vec::len).sum();
+    let mut order = Vec::with_capacity(total);
+    let mut last: Option<usize> = None;
+
+    while order.len() < total {
+        let remaining = total - order.len();
+        let candidates: Vec<usize> = (0..groups.len())
+            .filter(|&g| !groups[g].is_empty() && Some(g) != last)
+            .collect();
+
+        let group = if candidates.is_empty() {
+            // Only the previous group has tracks left.
+            last.unwrap_or_default()
+        } else if let Some(&largest) = candidates
+            .iter()
+            .max_by_key(|&&g| groups[g].len())
+            .filter(|&&g| groups[g].len() * 2 > remaining)
+        {
+            // A group holding most of what is left has to go now to stay spread out.
+            largest
+        } else {
+            *candidates
+                .choose_weighted(rng, |&g| groups[g].len())
+                .unwrap_or(&candidates[0])
+        };
+
+        if let Some(index) = groups[group].pop() {
+            order.push(index);
+        }
+        last = Some(group);
+    }
+    order
+}
+
+fn rating_weight(track: &Track) -> f64 {
+    1.0 + track.rating as f64 + if track.favorite { 2.0 } else { 0.0 }
+}
+
+fn recency_weight(last_played: Option<&u64>, now: u64) -> f64 {
+    match last_played {
+        Some(&played) => {
+            let days = now.saturating_sub(played) as f64 / (24.0 * 60.0 * 60.0);
+            (MIN_RECENCY_WEIGHT + days / RECENCY_DAYS).min(1.0)
+        }
+        None => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn is_permutation(order: &[usize], len: usize) -> bool {
+        let mut sorted = order.to_vec();
+        sorted.sort_unstable();
+        sorted == (0..len).collect::<Vec<_>>()
+    }
+
+    /// Fewest same-group neighbours possible: only what the largest group
+    /// has beyond one more than everything else.
+    fn unavoidable_repeats(sizes: &[usize]) -> usize {
+        let total: usize = sizes.iter().sum();
+        let largest = sizes.iter().copied().max().unwrap_or(0);
+        largest.saturating_sub(total - largest + 1)
+    }
+
+    #[test]
+    fn spread_places_every_track_once_and_keeps_groups_apart() {
+        let mut rng = StdRng::seed_from_u64(44);
+        for _ in 0..500 {
+            let sizes: Vec<usize> = (0..rng.gen_range(1..6))
+                .map(|_| rng.gen_range(0..8))
+                .collect();
+            let mut groups = Vec::new();
+            let mut group_of = Vec::new();
+            for (g, &size) in sizes.iter().enumerate() {
+                groups.push((group_of.len()..group_of.len() + size).collect());
+                group_of.extend(std::iter::repeat_n(g, size));
+            }
+
+            let order = spread(groups, &mut rng);
+            assert!(is_permutation(&order, group_of.len()), "{:?}", sizes);
+            let repeats = order
+                .windows(2)
+                .filter(|pair| group_of[pair[0]] == group_of[pair[1]])
+                .count();
+            assert_eq!(repeats, unavoidable_repeats(&sizes), "{:?}", sizes);
+        }
+    }
+
+    #[test]
+    fn weighted_shuffle_favours_rated_and_unplayed_tracks() {
+        let mut tracks: Vec<Track> = (0..6)
+            .map(|i| Track::from_path(PathBuf::from(format!("/m/{}.mp3", i))))
+            .collect();
+        tracks[0].rating = 5;
+        tracks[0].favorite = true;
+        let now = 1000 * DAY;
+        let last_played = HashMap::from([(tracks[1].path.as_path(), now)]);
+
+        let mut rng = StdRng::seed_from_u64(44);
+        let mut firsts = [0; 6];
+        let mut lasts = [0; 6];
+        for _ in 0..2000 {
+            let order = weighted(&tracks, &last_played, now, &mut rng);
+            assert!(is_permutation(&order, tracks.len()));
+            firsts[order[0]] += 1;
+            lasts[order[5]] += 1;
+        }
+        // The favorite weighs 8, the rest 1 and the track just played 0.1.
+        assert!(firsts[0] > 1000, "{:?}", firsts);
+        assert!(firsts[1] < firsts[2], "{:?}", firsts);
+        assert!(lasts[1] > lasts[2] * 3, "{:?}", lasts);
+    }
+
+    #[test]
+    fn recency_weight_recovers_over_time() {
+        let now = 1000 * DAY;
+        assert_eq!(recency_weight(None, now), 1.0);
+        assert_eq!(recency_weight(Some(&now), now), MIN_RECENCY_WEIGHT);
+        let half = recency_weight(Some(&(now - 15 * DAY)), now);
+        assert!((half - (MIN_RECENCY_WEIGHT + 0.5)).abs() < 1e-9);
+        assert_eq!(recency_weight(Some(&(now - 60 * DAY)), now), 1.0);
+    }
+}
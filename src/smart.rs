@@ -0,0 +1,482 @@
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::collate::Collator;
+use crate::scanner::Track;
+
+/// Track field a smart playlist rule or sort refers to.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    Year,
+    Duration,
+    Rating,
+    Favorite,
+    Plays,
+    Path,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "title" => Some(Self::Title),
+            "artist" => Some(Self::Artist),
+            "album" => Some(Self::Album),
+            "genre" => Some(Self::Genre),
+            "year" => Some(Self::Year),
+            "duration" | "length" => Some(Self::Duration),
+            "rating" | "stars" => Some(Self::Rating),
+            "favorite" | "fav" => Some(Self::Favorite),
+            "plays" | "playcount" => Some(Self::Plays),
+            "path" | "file" => Some(Self::Path),
+            _ => None,
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Self::Year | Self::Duration | Self::Rating | Self::Favorite | Self::Plays
+        )
+    }
+
+    fn text(&self, track: &Track) -> String {
+        match self {
+            Self::Title => track.title.to_lowercase(),
+            Self::Artist => track.artist.to_lowercase(),
+            Self::Album => track.album.as_deref().unwrap_or_default().to_lowercase(),
+            Self::Genre => track.genre.as_deref().unwrap_or_default().to_lowercase(),
+            Self::Year => track.year.map(|y| y.to_string()).unwrap_or_default(),
+            Self::Duration => track.duration.to_string(),
+            Self::Rating => track.rating.to_string(),
+            Self::Favorite => u8::from(track.favorite).to_string(),
+            Self::Plays => track.plays.to_string(),
+            Self::Path => track.path.to_string_lossy().to_lowercase(),
+        }
+    }
+
+    fn number(&self, track: &Track) -> Option<u64> {
+        match self {
+            Self::Year => track.year.map(u64::from),
+            Self::Duration => Some(track.duration),
+            Self::Rating => Some(track.rating.into()),
+            Self::Favorite => Some(track.favorite.into()),
+            Self::Plays => Some(track.plays.into()),
+            _ => None,
+        }
+    }
+
+    /// Orders text the way the track table does, through the collator.
+    fn compare(&self, a: &Track, b: &Track, collator: &Collator) -> Ordering {
+        let names = |a: &str, a_tag: Option<&str>, b: &str, b_tag: Option<&str>| {
+            collator.compare(collator.sort_name(a, a_tag), collator.sort_name(b, b_tag))
+        };
+        match self {
+            Self::Title => names(
+                &a.title,
+                a.title_sort.as_deref(),
+                &b.title,
+                b.title_sort.as_deref(),
+            ),
+            Self::Artist => names(
+                &a.artist,
+                a.artist_sort.as_deref(),
+                &b.artist,
+                b.artist_sort.as_deref(),
+            ),
+            Self::Album => names(
+                a.album.as_deref().unwrap_or_default(),
+                a.album_sort.as_deref(),
+                b.album.as_deref().unwrap_or_default(),
+                b.album_sort.as_deref(),
+            ),
+            Self::Genre => collator.compare(
+                a.genre.as_deref().unwrap_or_default(),
+                b.genre.as_deref().unwrap_or_default(),
+            ),
+            Self::Path => collator.compare_paths(&a.path, &b.path),
+            _ => self.number(a).cmp(&self.number(b)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Is,
+    IsNot,
+    Contains,
+    NotContains,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Between,
+}
+
+/// Operators in the order they are tried, so `<=` wins over `<`.
+const OPERATORS: &[(&str, Op)] = &[
+    ("not contains", Op::NotContains),
+    ("contains", Op::Contains),
+    ("between", Op::Between),
+    ("is not", Op::IsNot),
+    ("is", Op::Is),
+    ("!=", Op::IsNot),
+    ("!~", Op::NotContains),
+    ("<=", Op::LessOrEqual),
+    (">=", Op::GreaterOrEqual),
+    ("=", Op::Is),
+    ("~", Op::Contains),
+    ("<", Op::Less),
+    (">", Op::Greater),
+];
+
+/// A single condition such as `artist contains miles` or `year 1950-1969`.
+struct Rule {
+    field: Field,
+    op: Op,
+    text: String,
+    range: (u64, u64),
+}
+
+impl Rule {
+    fn parse(rule: &str) -> Result<Self, String> {
+        let rule = rule.trim();
+        let (name, rest) = rule.split_once(char::is_whitespace).unwrap_or((rule, ""));
+        let field = Field::parse(name).ok_or_else(|| format!("Unknown field '{}'", name))?;
+        let rest = rest.trim_start();
+
+        let (op, value) = OPERATORS
+            .iter()
+            .find_map(|(token, op)| {
+                let value = strip_prefix_ignore_case(rest, token)?;
+                let is_word = token.starts_with(char::is_alphabetic);
+                if is_word && !value.is_empty() && !value.starts_with(char::is_whitespace) {
+                    return None;
+                }
+                Some((*op, value.trim()))
+            })
+            .unwrap_or((Op::Is, rest));
+
+        if value.is_empty() {
+            return Err(format!("Missing value in '{}'", rule));
+        }
+
+        let mut parsed = Self {
+            field,
+            op,
+            text: value.to_lowercase(),
+            range: (0, u64::MAX),
+        };
+
+        if field.is_numeric() {
+            let range = parse_range(field, value)
+                .ok_or_else(|| format!("Invalid {} in '{}'", name, rule))?;
+            if range.0 != range.1 && op == Op::Is {
+                parsed.op = Op::Between;
+            } else if range.0 == range.1 && op == Op::Between {
+                return Err(format!("Expected a range in '{}'", rule));
+            }
+            parsed.range = range;
+        } else if matches!(
+            op,
+            Op::Less | Op::LessOrEqual | Op::Greater | Op::GreaterOrEqual | Op::Between
+        ) {
+            return Err(format!("'{}' only supports text comparisons", name));
+        }
+
+        Ok(parsed)
+    }
+
+    fn matches(&self, track: &Track) -> bool {
+        if self.field.is_numeric() {
+            let Some(value) = self.field.number(track) else {
+                return self.op == Op::IsNot;
+            };
+            let (low, high) = self.range;
+            return match self.op {
+                Op::Is | Op::Between => value >= low && value <= high,
+                Op::IsNot => value < low || value > high,
+                Op::Less => value < low,
+                Op::LessOrEqual => value <= high,
+                Op::Greater => value > high,
+                Op::GreaterOrEqual => value >= low,
+                Op::Contains => self.field.text(track).contains(&self.text),
+                Op::NotContains => !self.field.text(track).contains(&self.text),
+            };
+        }
+
+        let value = self.field.text(track);
+        match self.op {
+            Op::Is => value == self.text,
+            Op::IsNot => value != self.text,
+            Op::Contains => value.contains(&self.text),
+            Op::NotContains => !value.contains(&self.text),
+            _ => false,
+        }
+    }
+}
+
+/// Rule-based playlist definition, stored as JSON in the playlists directory.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmartRules {
+    pub rules: Vec<String>,
+    /// Match tracks satisfying any rule instead of all of them.
+    pub match_any: bool,
+    pub sort: Option<Field>,
+    pub descending: bool,
+    pub limit: Option<usize>,
+}
+
+impl SmartRules {
+    /// Parses the `name: rule; rule` shorthand accepted by the UI prompt.
+    pub fn parse(text: &str) -> Result<(String, Self), String> {
+        let (name, rules) = text.split_once(':').ok_or("Expected 'name: rule; rule'")?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Missing playlist name".to_string());
+        }
+
+        let smart = Self {
+            rules: rules
+                .split(';')
+                .map(|r| r.trim().to_string())
+                .filter(|r| !r.is_empty())
+                .collect(),
+            ..Self::default()
+        };
+        smart.compile()?;
+        Ok((name.to_string(), smart))
+    }
+
+    fn compile(&self) -> Result<Vec<Rule>, String> {
+        self.rules.iter().map(|r| Rule::parse(r)).collect()
+    }
+
+    /// Checks that every rule can be parsed.
+    pub fn validate(&self) -> Result<(), String> {
+        self.compile().map(|_| ())
+    }
+
+    /// Paths of the library tracks matching the rules, sorted and limited.
+    pub fn evaluate(&self, tracks: &[Track], collator: &Collator) -> Vec<PathBuf> {
+        let Ok(rules) = self.compile() else {
+            return Vec::new();
+        };
+
+        let mut matched: Vec<&Track> = tracks
+            .iter()
+            .filter(|track| {
+                if rules.is_empty() {
+                    true
+                } else if self.match_any {
+                    rules.iter().any(|r| r.matches(track))
+                } else {
+                    rules.iter().all(|r| r.matches(track))
+                }
+            })
+            .collect();
+
+        if let Some(field) = self.sort {
+            matched.sort_by(|a, b| field.compare(a, b, collator));
+            if self.descending {
+                matched.reverse();
+            }
+        }
+
+        matched
+            .into_iter()
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|t| t.path.clone())
+            .collect()
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &text[prefix.len()..])
+}
+
+/// Parses a single value or an inclusive `low-high` / `low..high` range.
+fn parse_range(field: Field, value: &str) -> Option<(u64, u64)> {
+    let bounds = value
+        .split_once("..")
+        .or_else(|| value.split_once('–'))
+        .or_else(|| value.split_once('-'));
+
+    match bounds {
+        Some((low, high)) => {
+            let low = parse_number(field, low.trim())?;
+            let high = parse_number(field, high.trim())?;
+            Some((low.min(high), low.max(high)))
+        }
+        None => parse_number(field, value).map(|n| (n, n)),
+    }
+}
+
+/// Reads a number, a yes/no favorite flag, or a duration as seconds,
+/// `m:ss`, `5min` or `90s`.
+fn parse_number(field: Field, value: &str) -> Option<u64> {
+    let value = value.trim().to_lowercase();
+    if field == Field::Favorite {
+        return match value.as_str() {
+            "yes" | "true" | "1" => Some(1),
+            "no" | "false" | "0" => Some(0),
+            _ => None,
+        };
+    }
+    if field != Field::Duration {
+        return value.parse().ok();
+    }
+
+    if let Some((minutes, seconds)) = value.split_once(':') {
+        return Some(minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?);
+    }
+    if let Some(minutes) = value
+        .strip_suffix("min")
+        .or_else(|| value.strip_suffix('m'))
+    {
+        return minutes.trim().parse::<u64>().ok().map(|m| m * 60);
+    }
+    value
+        .strip_suffix('s')
+        .unwrap_or(&value)
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn track(path: &str, artist: &str, year: Option<u32>, duration: u64, rating: u8) -> Track {
+        let mut track = Track::from_path(PathBuf::from(path));
+        track.artist = artist.to_string();
+        track.year = year;
+        track.duration = duration;
+        track.rating = rating;
+        track
+    }
+
+    fn library() -> Vec<Track> {
+        vec![
+            track("/m/so what.mp3", "Miles Davis", Some(1959), 562, 5),
+            track("/m/naima.mp3", "John Coltrane", Some(1960), 261, 4),
+            track("/m/track 10.mp3", "The Beatles", Some(1969), 185, 0),
+            track("/m/track 2.mp3", "Björk", None, 90, 3),
+        ]
+    }
+
+    fn names(paths: Vec<PathBuf>) -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| p.file_stem().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    fn evaluate(rules: SmartRules, tracks: &[Track]) -> Vec<String> {
+        names(rules.evaluate(tracks, &Collator::new(&Config::default())))
+    }
+
+    fn rules(rules: &[&str]) -> SmartRules {
+        SmartRules {
+            rules: rules.iter().map(|r| r.to_string()).collect(),
+            ..SmartRules::default()
+        }
+    }
+
+    #[test]
+    fn parses_operators_fields_and_ranges() {
+        let rule = Rule::parse("Artist contains miles").unwrap();
+        assert!(rule.field == Field::Artist && rule.op == Op::Contains);
+        assert_eq!(rule.text, "miles");
+
+        let rule = Rule::parse("year 1950-1969").unwrap();
+        assert!(rule.op == Op::Between && rule.range == (1950, 1969));
+        let rule = Rule::parse("year between 1969..1950").unwrap();
+        assert!(rule.op == Op::Between && rule.range == (1950, 1969));
+
+        assert!(Rule::parse("rating <= 3").unwrap().op == Op::LessOrEqual);
+        assert!(Rule::parse("title is not x").unwrap().op == Op::IsNot);
+        assert!(Rule::parse("title isolation").unwrap().op == Op::Is);
+        assert_eq!(Rule::parse("length > 5:30").unwrap().range, (330, 330));
+        assert_eq!(Rule::parse("duration < 5min").unwrap().range, (300, 300));
+        assert_eq!(Rule::parse("fav yes").unwrap().range, (1, 1));
+        assert!(Rule::parse("playcount >= 10").unwrap().field == Field::Plays);
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        assert!(Rule::parse("mood happy").is_err());
+        assert!(Rule::parse("artist contains").is_err());
+        assert!(Rule::parse("year recent").is_err());
+        assert!(Rule::parse("year between 1960").is_err());
+        assert!(Rule::parse("artist > m").is_err());
+        assert!(SmartRules::parse("no colon").is_err());
+        assert!(SmartRules::parse(": rating 5").is_err());
+
+        let (name, smart) = SmartRules::parse(" Best : rating 5; ; year 1959 ").unwrap();
+        assert_eq!(name, "Best");
+        assert_eq!(smart.rules, ["rating 5", "year 1959"]);
+    }
+
+    #[test]
+    fn matches_all_or_any_rule() {
+        let tracks = library();
+        assert_eq!(
+            evaluate(rules(&["rating >= 4", "year < 1960"]), &tracks),
+            ["so what"]
+        );
+        let any = SmartRules {
+            match_any: true,
+            ..rules(&["rating >= 4", "year < 1960"])
+        };
+        assert_eq!(evaluate(any, &tracks), ["so what", "naima"]);
+        assert_eq!(evaluate(rules(&["artist !~ e"]), &tracks), ["track 2"]);
+        // A track without a year only matches negated year rules.
+        assert_eq!(evaluate(rules(&["year != 1959"]), &tracks).len(), 3);
+        assert_eq!(evaluate(rules(&[]), &tracks).len(), 4);
+    }
+
+    #[test]
+    fn sorts_through_the_collator_and_limits() {
+        let tracks = library();
+        let by_artist = SmartRules {
+            sort: Some(Field::Artist),
+            ..SmartRules::default()
+        };
+        assert_eq!(
+            evaluate(by_artist, &tracks),
+            ["track 10", "track 2", "naima", "so what"]
+        );
+
+        let by_title = SmartRules {
+            sort: Some(Field::Title),
+            descending: true,
+            limit: Some(3),
+            ..SmartRules::default()
+        };
+        assert_eq!(
+            evaluate(by_title, &tracks),
+            ["track 10", "track 2", "so what"]
+        );
+    }
+
+    #[test]
+    fn follows_play_count_changes() {
+        let mut tracks = library();
+        let played = rules(&["plays >= 2"]);
+        assert!(evaluate(played.clone(), &tracks).is_empty());
+        tracks[1].plays = 2;
+        assert_eq!(evaluate(played, &tracks), ["naima"]);
+    }
+}
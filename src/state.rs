@@ -1,30 +1,84 @@
-use crate::app::{RepeatMode, SortMode};
+use crate::app::RepeatMode;
+use crate::columns::{Column, SortKey, SortSpec};
+use crate::player::gain_to_level;
+use crate::shuffle::ShuffleMode;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppState {
+    /// Linear amplitude, kept so older versions can still read the file.
     pub volume: f32,
+    /// Perceptual volume level; absent in states written before the dB curve.
+    pub volume_level: Option<f32>,
+    pub balance: f32,
+    pub mono: bool,
+    pub swap_channels: bool,
+    pub trim_silence: bool,
     pub shuffle: bool,
+    pub shuffle_mode: ShuffleMode,
+    pub radio: bool,
     pub repeat_mode: RepeatMode,
-    pub sort_mode: SortMode,
+    /// Table sort; absent in states written before sortable columns.
+    pub sort: Option<SortSpec>,
+    /// Single sort mode from those older states, read but no longer written.
+    #[serde(skip_serializing)]
+    pub sort_mode: Option<SortMode>,
+    /// Track table columns resized in the UI.
+    pub column_widths: HashMap<Column, u16>,
     pub last_track_path: Option<PathBuf>,
+    /// Offset into `last_track_path` when tune was closed, in milliseconds.
+    pub last_position_ms: u64,
+    pub queue: Vec<PathBuf>,
+    /// Shuffled library order, restored only while shuffle stays on.
+    pub play_order: Vec<PathBuf>,
+    pub order_index: Option<usize>,
+    pub imported_playlists: Vec<PathBuf>,
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
             volume: 1.0,
+            volume_level: None,
+            balance: 0.0,
+            mono: false,
+            swap_channels: false,
+            trim_silence: false,
             shuffle: false,
+            shuffle_mode: ShuffleMode::Tracks,
+            radio: false,
             repeat_mode: RepeatMode::Off,
-            sort_mode: SortMode::Filename,
+            sort: None,
+            sort_mode: None,
+            column_widths: HashMap::new(),
             last_track_path: None,
+            last_position_ms: 0,
+            queue: Vec::new(),
+            play_order: Vec::new(),
+            order_index: None,
+            imported_playlists: Vec::new(),
         }
     }
 }
 
 impl AppState {
+    pub fn volume_level(&self) -> f32 {
+        self.volume_level
+            .unwrap_or_else(|| gain_to_level(self.volume))
+    }
+
+    pub fn sort(&self) -> SortSpec {
+        match (&self.sort, self.sort_mode) {
+            (Some(sort), _) => sort.clone(),
+            (None, Some(mode)) => mode.to_spec(),
+            (None, None) => SortSpec::default(),
+        }
+    }
+
     pub fn load() -> Self {
         if let Some(mut path) = dirs::data_dir() {
             path.push("tune");
@@ -52,3 +106,49 @@ impl AppState {
         }
     }
 }
+
+/// Sort modes offered before the track table had sortable columns.
+#[derive(Clone, Copy, Deserialize)]
+pub enum SortMode {
+    Filename,
+    Title,
+    Artist,
+    Rating,
+}
+
+impl SortMode {
+    fn to_spec(self) -> SortSpec {
+        let key = |column, descending| SortKey { column, descending };
+        match self {
+            SortMode::Filename => SortSpec::default(),
+            SortMode::Title => SortSpec(vec![key(Column::Title, false)]),
+            SortMode::Artist => SortSpec(vec![key(Column::Artist, false)]),
+            SortMode::Rating => {
+                SortSpec(vec![key(Column::Rating, true), key(Column::Title, false)])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_the_old_sort_mode() {
+        let state: AppState = serde_json::from_str(r#"{"sort_mode": "Rating"}"#).unwrap();
+        assert_eq!(state.sort().describe(), "Rating↓ Title↑");
+        let state: AppState = serde_json::from_str(r#"{"sort_mode": "Artist"}"#).unwrap();
+        assert_eq!(state.sort().describe(), "Artist↑");
+        assert_eq!(AppState::default().sort().describe(), "File↑");
+
+        let saved = AppState {
+            sort: Some(state.sort()),
+            ..AppState::default()
+        };
+        let content = serde_json::to_string(&saved).unwrap();
+        assert!(!content.contains("sort_mode"));
+        let reloaded: AppState = serde_json::from_str(&content).unwrap();
+        assert_eq!(reloaded.sort().describe(), "Artist↑");
+    }
+}
@@ -0,0 +1,410 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::config::{ScrobbleConfig, ScrobbleService};
+use crate::scanner::Track;
+
+const LISTENBRAINZ_URL: &str = "https://api.listenbrainz.org";
+const LASTFM_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+
+/// Largest batch either service accepts in one request.
+const BATCH_SIZE: usize = 50;
+
+/// How long to wait before retrying after a failed submission.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A play as submitted to the scrobbling service.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Listen {
+    pub artist: String,
+    pub title: String,
+    #[serde(default)]
+    pub album: Option<String>,
+    #[serde(default)]
+    pub track_number: Option<u32>,
+    pub duration_secs: u64,
+    /// Unix time the play started, in seconds.
+    pub started_at: u64,
+}
+
+impl Listen {
+    pub fn new(track: &Track, started_at: u64) -> Self {
+        Self {
+            artist: track.artist.clone(),
+            title: track.title.clone(),
+            album: track.album.clone(),
+            track_number: track.track_number,
+            duration_secs: track.duration,
+            started_at,
+        }
+    }
+}
+
+enum Job {
+    NowPlaying(Listen),
+    /// New scrobbles are waiting in the shared queue.
+    Flush,
+}
+
+enum SubmitError {
+    /// Network trouble or a server error; the listens stay queued.
+    Retry(String),
+    /// The service refused the listens; retrying would not help.
+    Rejected(String),
+    /// The token or session key was refused; nothing will get through.
+    Unauthorized(String),
+}
+
+/// Hands plays to a background thread that submits them and keeps
+/// unsent scrobbles in a retry queue in the tune data directory.
+pub struct Submitter {
+    jobs: Sender<Job>,
+    messages: Receiver<String>,
+    /// Unsent scrobbles, shared with the worker and mirrored on disk.
+    queue: Arc<Mutex<Vec<Listen>>>,
+}
+
+impl Submitter {
+    pub fn start(config: ScrobbleConfig) -> Self {
+        let (jobs, job_receiver) = mpsc::channel();
+        let (message_sender, messages) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(load_queue()));
+
+        let worker_queue = Arc::clone(&queue);
+        thread::spawn(move || {
+            let mut worker = Worker {
+                client: Client::new(config),
+                queue: worker_queue,
+                messages: message_sender,
+                failing: false,
+                stopped: false,
+            };
+            worker.run(job_receiver);
+        });
+
+        Self {
+            jobs,
+            messages,
+            queue,
+        }
+    }
+
+    pub fn now_playing(&self, listen: Listen) {
+        self.jobs.send(Job::NowPlaying(listen)).ok();
+    }
+
+    /// Queues a scrobble. It is on disk before this returns, so a play
+    /// finished right before quitting is sent on the next start.
+    pub fn scrobble(&self, listen: Listen) {
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.push(listen);
+            save_queue(&queue);
+        }
+        self.jobs.send(Job::Flush).ok();
+    }
+
+    /// Status reports from the background thread, if any arrived.
+    pub fn poll_message(&self) -> Option<String> {
+        self.messages.try_recv().ok()
+    }
+}
+
+struct Worker {
+    client: Client,
+    queue: Arc<Mutex<Vec<Listen>>>,
+    messages: Sender<String>,
+    failing: bool,
+    /// Set once the credentials are refused; queued scrobbles wait on disk
+    /// for the next start.
+    stopped: bool,
+}
+
+impl Worker {
+    fn run(&mut self, jobs: Receiver<Job>) {
+        // Start with a flush of whatever the previous session left queued.
+        let mut next_retry = Some(Instant::now());
+
+        loop {
+            let job = match next_retry {
+                Some(at) => jobs.recv_timeout(at.saturating_duration_since(Instant::now())),
+                None => jobs.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match job {
+                Ok(Job::NowPlaying(listen)) => {
+                    // Now playing updates are only useful right away, so they are never queued.
+                    if let Err(SubmitError::Unauthorized(e)) = self.client.now_playing(&listen) {
+                        self.stop(e);
+                        return;
+                    }
+                    continue;
+                }
+                Ok(Job::Flush) if self.failing => continue,
+                Ok(Job::Flush) => {}
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let flushed = self.flush();
+            if self.stopped {
+                return;
+            }
+            next_retry = if flushed {
+                None
+            } else {
+                Some(Instant::now() + RETRY_INTERVAL)
+            };
+        }
+    }
+
+    /// Submits queued listens in batches. Returns false if some are left for a retry.
+    fn flush(&mut self) -> bool {
+        loop {
+            // The lock is not held during the request; new scrobbles only
+            // ever join the end of the queue, so the batch stays at the front.
+            let batch: Vec<Listen> = match self.queue.lock() {
+                Ok(queue) => queue.iter().take(BATCH_SIZE).cloned().collect(),
+                Err(_) => return false,
+            };
+            if batch.is_empty() {
+                break;
+            }
+            match self.client.scrobble(&batch) {
+                Ok(()) => self.remove_sent(batch.len()),
+                Err(SubmitError::Rejected(e)) => {
+                    self.remove_sent(batch.len());
+                    self.report(format!("Scrobbles rejected: {}", e));
+                }
+                Err(SubmitError::Unauthorized(e)) => {
+                    self.stop(e);
+                    return false;
+                }
+                Err(SubmitError::Retry(e)) => {
+                    if !self.failing {
+                        self.failing = true;
+                        let queued = self.queue.lock().map_or(0, |queue| queue.len());
+                        self.report(format!(
+                            "Scrobbling failed, {} queued for retry: {}",
+                            queued, e
+                        ));
+                    }
+                    return false;
+                }
+            }
+        }
+
+        if self.failing {
+            self.failing = false;
+            self.report("Queued scrobbles submitted".to_string());
+        }
+        true
+    }
+
+    fn remove_sent(&self, count: usize) {
+        if let Ok(mut queue) = self.queue.lock() {
+            let count = count.min(queue.len());
+            queue.drain(..count);
+            save_queue(&queue);
+        }
+    }
+
+    fn stop(&mut self, error: String) {
+        self.stopped = true;
+        self.report(format!(
+            "Scrobbling stopped, check the credentials in the config: {}",
+            error
+        ));
+    }
+
+    fn report(&self, message: String) {
+        self.messages.send(message).ok();
+    }
+}
+
+struct Client {
+    agent: ureq::Agent,
+    config: ScrobbleConfig,
+}
+
+impl Client {
+    fn new(config: ScrobbleConfig) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build(),
+            config,
+        }
+    }
+
+    fn base_url(&self) -> &str {
+        match (&self.config.base_url, self.config.service) {
+            (Some(url), _) => url,
+            (None, ScrobbleService::ListenBrainz) => LISTENBRAINZ_URL,
+            (None, ScrobbleService::LastFm) => LASTFM_URL,
+        }
+    }
+
+    fn now_playing(&self, listen: &Listen) -> Result<(), SubmitError> {
+        match self.config.service {
+            ScrobbleService::ListenBrainz => {
+                self.listenbrainz("playing_now", std::slice::from_ref(listen))
+            }
+            ScrobbleService::LastFm => {
+                let mut params = vec![("method".to_string(), "track.updateNowPlaying".to_string())];
+                params.extend(lastfm_track_params(listen, None));
+                self.lastfm(params)
+            }
+        }
+    }
+
+    fn scrobble(&self, listens: &[Listen]) -> Result<(), SubmitError> {
+        match self.config.service {
+            ScrobbleService::ListenBrainz => {
+                let listen_type = if listens.len() == 1 {
+                    "single"
+                } else {
+                    "import"
+                };
+                self.listenbrainz(listen_type, listens)
+            }
+            ScrobbleService::LastFm => {
+                let mut params = vec![("method".to_string(), "track.scrobble".to_string())];
+                for (i, listen) in listens.iter().enumerate() {
+                    params.extend(lastfm_track_params(listen, Some(i)));
+                }
+                self.lastfm(params)
+            }
+        }
+    }
+
+    fn listenbrainz(&self, listen_type: &str, listens: &[Listen]) -> Result<(), SubmitError> {
+        let payload: Vec<serde_json::Value> = listens
+            .iter()
+            .map(|listen| {
+                let mut entry = json!({
+                    "track_metadata": {
+                        "artist_name": listen.artist,
+                        "track_name": listen.title,
+                        "additional_info": {
+                            "duration": listen.duration_secs,
+                            "media_player": "tune",
+                            "submission_client": "tune",
+                            "submission_client_version": env!("CARGO_PKG_VERSION"),
+                        },
+                    },
+                });
+                if let Some(album) = &listen.album {
+                    entry["track_metadata"]["release_name"] = json!(album);
+                }
+                if let Some(number) = listen.track_number {
+                    entry["track_metadata"]["additional_info"]["tracknumber"] = json!(number);
+                }
+                if listen_type != "playing_now" {
+                    entry["listened_at"] = json!(listen.started_at);
+                }
+                entry
+            })
+            .collect();
+
+        let url = format!("{}/1/submit-listens", self.base_url().trim_end_matches('/'));
+        let result = self
+            .agent
+            .post(&url)
+            .set("Authorization", &format!("Token {}", self.config.token))
+            .send_json(json!({ "listen_type": listen_type, "payload": payload }));
+        check_response(result)
+    }
+
+    fn lastfm(&self, mut params: Vec<(String, String)>) -> Result<(), SubmitError> {
+        params.push(("api_key".to_string(), self.config.api_key.clone()));
+        params.push(("sk".to_string(), self.config.session_key.clone()));
+        params.sort();
+
+        let mut signature: String = params.iter().map(|(k, v)| format!("{}{}", k, v)).collect();
+        signature.push_str(&self.config.api_secret);
+        params.push((
+            "api_sig".to_string(),
+            format!("{:x}", md5::compute(signature)),
+        ));
+        params.push(("format".to_string(), "json".to_string()));
+
+        let form: Vec<(&str, &str)> = params
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        check_response(self.agent.post(self.base_url()).send_form(&form))
+    }
+}
+
+/// Track parameters for the Last.fm API, indexed (`artist[0]`) for batch scrobbles.
+fn lastfm_track_params(listen: &Listen, index: Option<usize>) -> Vec<(String, String)> {
+    let key = |name: &str| match index {
+        Some(i) => format!("{}[{}]", name, i),
+        None => name.to_string(),
+    };
+
+    let mut params = vec![
+        (key("artist"), listen.artist.clone()),
+        (key("track"), listen.title.clone()),
+        (key("duration"), listen.duration_secs.to_string()),
+    ];
+    if index.is_some() {
+        params.push((key("timestamp"), listen.started_at.to_string()));
+    }
+    if let Some(album) = &listen.album {
+        params.push((key("album"), album.clone()));
+    }
+    if let Some(number) = listen.track_number {
+        params.push((key("trackNumber"), number.to_string()));
+    }
+    params
+}
+
+/// Server errors and rate limiting are retried later, bad credentials stop
+/// scrobbling, and other client errors mean the listens themselves were refused.
+fn check_response(result: Result<ureq::Response, ureq::Error>) -> Result<(), SubmitError> {
+    match result {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            let message = format!("HTTP {} {}", code, body.trim());
+            if code >= 500 || code == 429 {
+                Err(SubmitError::Retry(message))
+            } else if code == 401 || code == 403 {
+                Err(SubmitError::Unauthorized(message))
+            } else {
+                Err(SubmitError::Rejected(message))
+            }
+        }
+        Err(e) => Err(SubmitError::Retry(e.to_string())),
+    }
+}
+
+fn queue_path() -> Option<PathBuf> {
+    let mut path = dirs::data_dir()?;
+    path.push("tune");
+    fs::create_dir_all(&path).ok();
+    path.push("scrobble_queue.json");
+    Some(path)
+}
+
+fn load_queue() -> Vec<Listen> {
+    queue_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_queue(queue: &[Listen]) {
+    if let Some(path) = queue_path()
+        && let Ok(content) = serde_json::to_string_pretty(queue)
+    {
+        fs::write(path, content).ok();
+    }
+}
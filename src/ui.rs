@@ -1,13 +1,23 @@
 use ratatui::{
     Frame,
-    layout::{Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Gauge, HighlightSpacing, List, ListItem, Paragraph},
+    widgets::{
+        Block, Borders, Cell, Gauge, HighlightSpacing, List, ListItem, Paragraph, Row, Table,
+        TableState,
+    },
 };
 
-use crate::app::App;
+use crate::app::{App, View};
+use crate::columns::Column;
+use crate::config::VolumeDisplay;
+use crate::folders::FolderRow;
+use crate::history;
+use crate::library::LibraryColumn;
 use crate::player::PlaybackState;
+use crate::ratings::MAX_STARS;
+use crate::search;
 
 pub fn render(frame: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
@@ -22,7 +32,13 @@ pub fn render(frame: &mut Frame, app: &mut App) {
     if app.show_lyrics {
         render_lyrics(frame, app, chunks[0]);
     } else {
-        render_playlist(frame, app, chunks[0]);
+        match app.view {
+            View::Playlist => render_playlist(frame, app, chunks[0]),
+            View::Library => render_library(frame, app, chunks[0]),
+            View::Folders => render_folders(frame, app, chunks[0]),
+            View::Queue => render_queue(frame, app, chunks[0]),
+            View::Playlists => render_playlists(frame, app, chunks[0]),
+        }
     }
     render_now_playing(frame, app, chunks[1]);
     render_status_bar(frame, app, chunks[2]);
@@ -32,23 +48,95 @@ pub fn render(frame: &mut Frame, app: &mut App) {
         render_help_overlay(frame, area);
     }
 
+    if app.show_stats {
+        let area = centered_rect(80, 80, frame.area());
+        render_stats_overlay(frame, app, area);
+    }
+
     if let Some((msg, _)) = &app.status_message {
         let area = centered_rect(50, 15, frame.area());
         render_status_overlay(frame, msg, area);
     }
 }
 
+/// Background of rows marked for batch actions.
+const MARKED_BG: Color = Color::Rgb(60, 60, 110);
+
+/// Truncates or pads `text` to exactly `width` characters.
+fn fit_width(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    if len > width {
+        let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+        truncated.push('…');
+        truncated
+    } else {
+        format!("{}{}", text, " ".repeat(width - len))
+    }
+}
+
+fn rating_spans(stars: u8, favorite: bool, style: Style) -> Vec<Span<'static>> {
+    let stars = stars.min(MAX_STARS) as usize;
+    vec![
+        Span::styled(
+            format!(" {}", "★".repeat(stars)),
+            style.fg(Color::Rgb(255, 210, 80)),
+        ),
+        Span::styled(
+            "☆".repeat(MAX_STARS as usize - stars),
+            style.fg(Color::Rgb(90, 90, 90)),
+        ),
+        Span::styled(
+            if favorite { " ♥" } else { "  " },
+            style.fg(Color::Rgb(255, 90, 120)),
+        ),
+    ]
+}
+
 fn render_playlist(frame: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
     let selected = app.selected();
     let playing_index = app.playing_index;
-
-    let items: Vec<ListItem> = app
-        .tracks
+    let query = app.filter.as_ref().map(|f| f.query.as_str());
+    let range = app.visual_range();
+    let columns: Vec<(Column, u16)> = app
+        .columns
         .iter()
+        .map(|&column| (column, app.column_width(column)))
+        .collect();
+
+    let header = Row::new(
+        std::iter::once(Cell::from("")).chain(columns.iter().enumerate().map(
+            |(i, &(column, _))| {
+                let arrow = match app.sort.direction(column) {
+                    Some(true) => "↓",
+                    Some(false) => "↑",
+                    None => "",
+                };
+                let mut style = Style::default()
+                    .fg(Color::Rgb(100, 150, 255))
+                    .add_modifier(Modifier::BOLD);
+                if i == app.focused_column {
+                    style = style.add_modifier(Modifier::UNDERLINED);
+                }
+                let line = Line::styled(format!("{}{}", column.label(), arrow), style);
+                Cell::from(if column.right_aligned() {
+                    line.alignment(Alignment::Right)
+                } else {
+                    line
+                })
+            },
+        )),
+    );
+
+    let rows: Vec<Row> = app
+        .visible_tracks()
+        .into_iter()
         .enumerate()
-        .map(|(index, track)| {
+        .map(|(row, index)| {
+            let track = &app.tracks[index];
             let is_playing = playing_index == Some(index);
-            let is_selected = index == selected;
+            let is_selected = selected == Some(index);
+            let is_marked = app.marked_tracks.contains(&index)
+                || range.as_ref().is_some_and(|r| r.contains(&row));
 
             let style = match (is_selected, is_playing) {
                 (true, true) => Style::default()
@@ -60,27 +148,430 @@ fn render_playlist(frame: &mut Frame, app: &mut App, area: ratatui::layout::Rect
                 (false, true) => Style::default().fg(Color::Rgb(150, 255, 150)),
                 (false, false) => Style::default().fg(Color::Rgb(200, 200, 200)),
             };
+            let style = if is_marked {
+                style.bg(MARKED_BG)
+            } else {
+                style
+            };
 
-            let prefix = if is_playing { "▶ " } else { "  " };
-            let content = format!("{}{}", prefix, track.display_name());
-
-            ListItem::new(Line::from(Span::styled(content, style)))
+            let prefix = if is_playing { "▶" } else { "" };
+            let cells = columns.iter().map(|&(column, width)| {
+                if column == Column::Rating {
+                    return Cell::from(Line::from(rating_spans(
+                        track.rating,
+                        track.favorite,
+                        style,
+                    )));
+                }
+                let text = fit_width(&column.text(track), width as usize);
+                let line = match query {
+                    Some(query) if !column.right_aligned() => {
+                        Line::from(highlight_spans(&text, query, style))
+                    }
+                    _ => Line::styled(text, style),
+                };
+                Cell::from(if column.right_aligned() {
+                    line.alignment(Alignment::Right)
+                } else {
+                    line
+                })
+            });
+            Row::new(std::iter::once(Cell::from(Span::styled(prefix, style))).chain(cells))
+                .style(style)
         })
         .collect();
 
-    let list = List::new(items)
+    let title = match &app.filter {
+        Some(filter) => format!(
+            " Playlist /{} ({} of {}) [n/N] Next/Prev [Esc] Clear ",
+            filter.query,
+            filter.matches.len(),
+            app.tracks.len()
+        ),
+        None => " Playlist ".to_string(),
+    };
+
+    let widths = std::iter::once(Constraint::Length(1))
+        .chain(columns.iter().map(|&(_, width)| Constraint::Length(width)));
+    let table = Table::new(rows, widths)
+        .header(header)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(" Playlist ")
+                .title(title)
                 .border_style(Style::default().fg(Color::Rgb(100, 150, 255)))
                 .border_type(ratatui::widgets::BorderType::Rounded),
         )
         .highlight_symbol("▸ ")
         .highlight_spacing(HighlightSpacing::Always)
+        .row_highlight_style(Style::default().add_modifier(Modifier::BOLD));
+
+    // Navigation works on the list state shared with the other views.
+    let mut table_state = TableState::default()
+        .with_offset(app.list_state.offset())
+        .with_selected(app.list_state.selected());
+    frame.render_stateful_widget(table, area, &mut table_state);
+    *app.list_state.offset_mut() = table_state.offset();
+}
+
+/// Splits `text` into spans, marking the characters matched by the search query.
+fn highlight_spans(text: &str, query: &str, style: Style) -> Vec<Span<'static>> {
+    let positions = search::highlight_positions(query, text);
+    let highlight = style
+        .fg(Color::Rgb(255, 120, 220))
+        .add_modifier(Modifier::UNDERLINED);
+
+    let mut spans = Vec::new();
+    let mut current = String::new();
+    let mut current_matched = false;
+    for (i, c) in text.chars().enumerate() {
+        let matched = positions.binary_search(&i).is_ok();
+        if matched != current_matched && !current.is_empty() {
+            let style = if current_matched { highlight } else { style };
+            spans.push(Span::styled(std::mem::take(&mut current), style));
+        }
+        current_matched = matched;
+        current.push(c);
+    }
+    if !current.is_empty() {
+        let style = if current_matched { highlight } else { style };
+        spans.push(Span::styled(current, style));
+    }
+    spans
+}
+
+fn render_queue(frame: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Queue ({}) ", app.queue.len()))
+        .border_style(Style::default().fg(Color::Rgb(150, 220, 150)))
+        .border_type(ratatui::widgets::BorderType::Rounded);
+
+    if app.queue.is_empty() {
+        let paragraph = Paragraph::new("Queue is empty. Press [a] in the playlist to add tracks.")
+            .block(block)
+            .style(Style::default().fg(Color::Rgb(150, 150, 150)))
+            .alignment(ratatui::layout::Alignment::Center);
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let selected = app.queue_selected();
+    let range = app.visual_range();
+
+    let items: Vec<ListItem> = app
+        .queue
+        .iter()
+        .enumerate()
+        .map(|(pos, &index)| {
+            let style = if selected == Some(pos) {
+                Style::default()
+                    .fg(Color::Rgb(255, 200, 100))
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Rgb(200, 200, 200))
+            };
+            let style = if app.marked_queue.contains(&pos)
+                || range.as_ref().is_some_and(|r| r.contains(&pos))
+            {
+                style.bg(MARKED_BG)
+            } else {
+                style
+            };
+
+            let content = format!("{:>3}. {}", pos + 1, app.tracks[index].display_name());
+            ListItem::new(Line::from(Span::styled(content, style)))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_symbol("▸ ")
+        .highlight_spacing(HighlightSpacing::Always)
         .highlight_style(Style::default().add_modifier(Modifier::BOLD));
 
-    frame.render_stateful_widget(list, area, &mut app.list_state);
+    frame.render_stateful_widget(list, area, &mut app.queue_state);
+}
+
+fn render_library(frame: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(30),
+            Constraint::Percentage(30),
+            Constraint::Percentage(40),
+        ])
+        .split(area);
+
+    let playing_index = app.playing_index;
+    let item = |content: String, playing: bool| {
+        let style = if playing {
+            Style::default().fg(Color::Rgb(150, 255, 150))
+        } else {
+            Style::default().fg(Color::Rgb(200, 200, 200))
+        };
+        ListItem::new(Line::from(Span::styled(content, style)))
+    };
+
+    let artists: Vec<ListItem> = app
+        .library
+        .artists
+        .iter()
+        .map(|artist| {
+            let playing = artist
+                .albums
+                .iter()
+                .any(|a| playing_index.is_some_and(|i| a.tracks.contains(&i)));
+            item(artist.name.clone(), playing)
+        })
+        .collect();
+
+    let albums: Vec<ListItem> = app
+        .library_artist()
+        .map(|artist| {
+            artist
+                .albums
+                .iter()
+                .map(|album| {
+                    let playing = playing_index.is_some_and(|i| album.tracks.contains(&i));
+                    item(format!("{} ({})", album.name, album.tracks.len()), playing)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let tracks: Vec<ListItem> = app
+        .library_album()
+        .map(|album| {
+            album
+                .tracks
+                .iter()
+                .map(|&index| {
+                    let track = &app.tracks[index];
+                    let number = track
+                        .track_number
+                        .map(|n| format!("{:>2}. ", n))
+                        .unwrap_or_default();
+                    item(
+                        format!("{}{}", number, track.title),
+                        playing_index == Some(index),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let focused = app.library_column;
+    let column = |items: Vec<ListItem<'static>>, title: &'static str, column: LibraryColumn| {
+        let border_color = if focused == column {
+            Color::Rgb(255, 200, 100)
+        } else {
+            Color::Rgb(100, 150, 255)
+        };
+        List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(Style::default().fg(border_color))
+                    .border_type(ratatui::widgets::BorderType::Rounded),
+            )
+            .highlight_symbol("▸ ")
+            .highlight_spacing(HighlightSpacing::Always)
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Rgb(255, 200, 100))
+                    .add_modifier(Modifier::BOLD),
+            )
+    };
+
+    frame.render_stateful_widget(
+        column(artists, " Artists ", LibraryColumn::Artists),
+        columns[0],
+        &mut app.artist_state,
+    );
+    frame.render_stateful_widget(
+        column(albums, " Albums ", LibraryColumn::Albums),
+        columns[1],
+        &mut app.album_state,
+    );
+    frame.render_stateful_widget(
+        column(
+            tracks,
+            " Tracks [Enter] Play [a] Queue ",
+            LibraryColumn::Tracks,
+        ),
+        columns[2],
+        &mut app.library_track_state,
+    );
+}
+
+fn render_folders(frame: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " {} [Enter/l/h] Expand/Collapse [P] Play [a] Queue ",
+            app.config.music_dir.display()
+        ))
+        .border_style(Style::default().fg(Color::Rgb(100, 200, 200)))
+        .border_type(ratatui::widgets::BorderType::Rounded);
+
+    let playing_index = app.playing_index;
+    let items: Vec<ListItem> = app
+        .folder_tree
+        .rows()
+        .into_iter()
+        .map(|row| match row {
+            FolderRow::Folder { path, depth } => {
+                let (marker, name, count) = match app.folder_tree.folder(&path) {
+                    Some(folder) => {
+                        let marker = if app.folder_tree.expanded.contains(&path) {
+                            "▾"
+                        } else {
+                            "▸"
+                        };
+                        (marker, folder.name.clone(), folder.all_tracks().len())
+                    }
+                    None => ("▸", path.display().to_string(), 0),
+                };
+                let playing = playing_index.is_some_and(|i| app.tracks[i].path.starts_with(&path));
+                let color = if playing {
+                    Color::Rgb(150, 255, 150)
+                } else {
+                    Color::Rgb(130, 200, 255)
+                };
+                ListItem::new(Line::from(Span::styled(
+                    format!("{}{} {}/ ({})", "  ".repeat(depth), marker, name, count),
+                    Style::default().fg(color),
+                )))
+            }
+            FolderRow::Track { index, depth } => {
+                let track = &app.tracks[index];
+                let name = track
+                    .path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| track.display_name());
+                let (prefix, color) = if playing_index == Some(index) {
+                    ("▶ ", Color::Rgb(150, 255, 150))
+                } else {
+                    ("  ", Color::Rgb(200, 200, 200))
+                };
+                ListItem::new(Line::from(Span::styled(
+                    format!("{}{}{}", "  ".repeat(depth), prefix, name),
+                    Style::default().fg(color),
+                )))
+            }
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_symbol("▸ ")
+        .highlight_spacing(HighlightSpacing::Always)
+        .highlight_style(
+            Style::default()
+                .fg(Color::Rgb(255, 200, 100))
+                .add_modifier(Modifier::BOLD),
+        );
+
+    frame.render_stateful_widget(list, area, &mut app.folder_state);
+}
+
+fn render_playlists(frame: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Rgb(200, 150, 255)))
+        .border_type(ratatui::widgets::BorderType::Rounded);
+
+    let Some(open) = app.open_playlist else {
+        let block = block.title(" Playlists [n] New [S] Smart [Enter] Open [P] Play ");
+        if app.playlists.is_empty() {
+            let paragraph = Paragraph::new("No playlists yet. Press [n] to create one.")
+                .block(block)
+                .style(Style::default().fg(Color::Rgb(150, 150, 150)))
+                .alignment(ratatui::layout::Alignment::Center);
+            frame.render_widget(paragraph, area);
+            return;
+        }
+
+        let items: Vec<ListItem> = app
+            .playlists
+            .iter()
+            .map(|playlist| {
+                let kind = if playlist.smart.is_some() {
+                    " [smart]"
+                } else {
+                    ""
+                };
+                let content = format!("{}{} ({})", playlist.name, kind, playlist.entries.len());
+                ListItem::new(Line::from(Span::styled(
+                    content,
+                    Style::default().fg(Color::Rgb(200, 200, 200)),
+                )))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_symbol("▸ ")
+            .highlight_spacing(HighlightSpacing::Always)
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Rgb(255, 200, 100))
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        frame.render_stateful_widget(list, area, &mut app.playlist_state);
+        return;
+    };
+
+    let playlist = &app.playlists[open];
+    let kind = if playlist.smart.is_some() {
+        " [smart]"
+    } else {
+        ""
+    };
+    let block = block.title(format!(
+        " {}{} ({}) [Esc] Back ",
+        playlist.name,
+        kind,
+        playlist.entries.len()
+    ));
+
+    let items: Vec<ListItem> = playlist
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(pos, path)| {
+            let (content, style) = match app.track_index(path) {
+                Some(index) => (
+                    app.tracks[index].display_name(),
+                    Style::default().fg(Color::Rgb(200, 200, 200)),
+                ),
+                None => (
+                    format!("{} (missing)", path.display()),
+                    Style::default().fg(Color::Rgb(120, 120, 120)),
+                ),
+            };
+            ListItem::new(Line::from(Span::styled(
+                format!("{:>3}. {}", pos + 1, content),
+                style,
+            )))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_symbol("▸ ")
+        .highlight_spacing(HighlightSpacing::Always)
+        .highlight_style(
+            Style::default()
+                .fg(Color::Rgb(255, 200, 100))
+                .add_modifier(Modifier::BOLD),
+        );
+
+    frame.render_stateful_widget(list, area, &mut app.playlist_entry_state);
 }
 
 fn parse_lrc(lrc: &str) -> Vec<(std::time::Duration, String)> {
@@ -247,18 +738,12 @@ fn render_now_playing(frame: &mut Frame, app: &App, area: ratatui::layout::Rect)
         .as_deref()
         .unwrap_or("No track selected");
 
-    let next_track_info = if let Some(q_idx) = app.queue_index {
-        if q_idx + 1 < app.queue.len() {
-            let next_idx = app.queue[q_idx + 1];
-            format!("Next: {}", app.tracks[next_idx].title)
-        } else if app.repeat_mode == crate::app::RepeatMode::All && !app.queue.is_empty() {
-            let next_idx = app.queue[0];
-            format!("Next: {}", app.tracks[next_idx].title)
-        } else {
-            String::new()
+    let next_track_info = match app.peek_next() {
+        Some(next_idx) if !app.queue.is_empty() => {
+            format!("Next (queued): {}", app.tracks[next_idx].title)
         }
-    } else {
-        String::new()
+        Some(next_idx) => format!("Next: {}", app.tracks[next_idx].title),
+        None => String::new(),
     };
 
     let info_text = if next_track_info.is_empty() {
@@ -278,15 +763,26 @@ fn render_now_playing(frame: &mut Frame, app: &App, area: ratatui::layout::Rect)
         )
         .alignment(ratatui::layout::Alignment::Center);
 
-    let elapsed_secs = position.as_secs();
-    let total_duration_secs = if let Some(index) = app.playing_index {
-        app.tracks[index].duration
+    let track_duration = if let Some(index) = app.playing_index {
+        std::time::Duration::from_secs(app.tracks[index].duration)
     } else {
-        0
+        std::time::Duration::ZERO
+    };
+
+    let (elapsed, total_duration) = match app.player.trim_window() {
+        Some((start, end)) => (
+            position.saturating_sub(start),
+            end.unwrap_or(track_duration).saturating_sub(start),
+        ),
+        None => (position, track_duration),
     };
 
+    let elapsed_secs = elapsed.as_secs();
+    let total_duration_secs = total_duration.as_secs();
+
     let progress_ratio = if total_duration_secs > 0 {
-        elapsed_secs as f64 / total_duration_secs as f64
+        // The decoded-frame clock can run past the tagged duration.
+        (elapsed_secs as f64 / total_duration_secs as f64).min(1.0)
     } else {
         0.0
     };
@@ -312,11 +808,44 @@ fn render_now_playing(frame: &mut Frame, app: &App, area: ratatui::layout::Rect)
         .style(Style::default().fg(Color::Rgb(150, 150, 150)))
         .alignment(ratatui::layout::Alignment::Center);
 
-    let vol_percent = (app.player.volume * 100.0) as u8;
     let vol_text = if app.player.muted {
         "Volume: Muted".to_string()
     } else {
-        format!("Volume: {}%", vol_percent)
+        match app.config.volume_display {
+            VolumeDisplay::Percent => {
+                format!("Volume: {}%", (app.player.volume * 100.0).round() as u16)
+            }
+            VolumeDisplay::Decibels => match app.player.volume_db() {
+                Some(db) => format!("Volume: {:+.1} dB", db),
+                None => "Volume: -∞ dB".to_string(),
+            },
+        }
+    };
+
+    let channel_settings = app.player.channel_settings();
+    let mut output_flags = Vec::new();
+    if channel_settings.balance < 0.0 {
+        output_flags.push(format!(
+            "Bal: L{}%",
+            (-channel_settings.balance * 100.0).round() as u8
+        ));
+    } else if channel_settings.balance > 0.0 {
+        output_flags.push(format!(
+            "Bal: R{}%",
+            (channel_settings.balance * 100.0).round() as u8
+        ));
+    }
+    if channel_settings.mono {
+        output_flags.push("Mono".to_string());
+    }
+    if channel_settings.swap {
+        output_flags.push("L⇄R".to_string());
+    }
+
+    let vol_text = if output_flags.is_empty() {
+        vol_text
+    } else {
+        format!("{} | {}", vol_text, output_flags.join(" | "))
     };
 
     let vol_style = if app.player.muted {
@@ -347,26 +876,117 @@ fn render_status_bar(frame: &mut Frame, app: &App, area: ratatui::layout::Rect)
     let repeat_str = match app.repeat_mode {
         crate::app::RepeatMode::Off => "",
         crate::app::RepeatMode::All => "[Repeat: All] ",
+        crate::app::RepeatMode::Album => "[Repeat: Album] ",
         crate::app::RepeatMode::One => "[Repeat: One] ",
     };
 
-    let shuffle_str = if app.shuffle { "[Shuffle] " } else { "" };
-    let sort_str = match app.sort_mode {
-        crate::app::SortMode::Filename => "[Sort: File] ",
-        crate::app::SortMode::Title => "[Sort: Title] ",
-        crate::app::SortMode::Artist => "[Sort: Artist] ",
+    let shuffle_str = match app.shuffle_mode {
+        _ if !app.shuffle => String::new(),
+        crate::shuffle::ShuffleMode::Tracks => "[Shuffle] ".to_string(),
+        mode => format!("[Shuffle: {}] ", mode.label()),
+    };
+    let radio_str = if app.radio { "[Radio] " } else { "" };
+    let stop_str = match app.stop_after {
+        Some(1) => "[Stop after current] ".to_string(),
+        Some(count) => format!("[Stop after {}] ", count),
+        None => String::new(),
+    };
+    let trim_str = if app.player.trim_silence {
+        "[Trim] "
+    } else {
+        ""
+    };
+    let sort_str = format!("[Sort: {}] ", app.sort.describe());
+
+    if let Some(prompt) = &app.prompt {
+        let input = Paragraph::new(format!("{}: {}█", prompt.kind.label(), prompt.text))
+            .style(Style::default().fg(Color::Rgb(255, 220, 150)))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Input [Enter] Confirm [Esc] Cancel ")
+                    .border_style(Style::default().fg(Color::Rgb(255, 200, 100)))
+                    .border_type(ratatui::widgets::BorderType::Rounded),
+            );
+        frame.render_widget(input, area);
+        return;
+    }
+
+    let position = match app.view {
+        View::Playlist => match &app.filter {
+            Some(filter) => format!(
+                "Match {}/{}",
+                app.list_state.selected().map_or(0, |row| row + 1),
+                filter.matches.len()
+            ),
+            None => format!(
+                "Track {}/{}",
+                app.list_state.selected().map_or(0, |row| row + 1),
+                track_count
+            ),
+        },
+        View::Library => {
+            let (label, state, len) = match app.library_column {
+                LibraryColumn::Artists => ("Artist", &app.artist_state, app.library.artists.len()),
+                LibraryColumn::Albums => (
+                    "Album",
+                    &app.album_state,
+                    app.library_artist().map_or(0, |a| a.albums.len()),
+                ),
+                LibraryColumn::Tracks => (
+                    "Track",
+                    &app.library_track_state,
+                    app.library_album().map_or(0, |a| a.tracks.len()),
+                ),
+            };
+            format!(
+                "{} {}/{}",
+                label,
+                state.selected().map_or(0, |pos| pos + 1),
+                len
+            )
+        }
+        View::Folders => format!(
+            "Row {}/{}",
+            app.folder_state.selected().map_or(0, |pos| pos + 1),
+            app.folder_tree.rows().len()
+        ),
+        View::Queue => format!(
+            "Queue {}/{}",
+            app.queue_selected().map_or(0, |pos| pos + 1),
+            app.queue.len()
+        ),
+        View::Playlists => match app.open_playlist {
+            Some(index) => format!(
+                "Entry {}/{}",
+                app.playlist_entry_selected().map_or(0, |pos| pos + 1),
+                app.playlists[index].entries.len()
+            ),
+            None => format!(
+                "Playlist {}/{}",
+                app.selected_playlist().map_or(0, |pos| pos + 1),
+                app.playlists.len()
+            ),
+        },
+    };
+
+    let marked_str = match app.marked_summary() {
+        Some((count, secs)) => format!(
+            "[{} marked, {}:{:02}:{:02}] ",
+            count,
+            secs / 3600,
+            (secs % 3600) / 60,
+            secs % 60
+        ),
+        None => String::new(),
     };
 
     let status_text = if track_count == 0 {
         String::from("No tracks found")
     } else {
         format!(
-            "{}{}{}Track {}/{} | [h] Help | [q] Quit",
-            sort_str,
-            shuffle_str,
-            repeat_str,
-            app.selected() + 1,
-            track_count
+            "{}{}{}{}{}{}{}{} | [Tab] View | [h] Help | [q] Quit",
+            marked_str, sort_str, shuffle_str, radio_str, repeat_str, stop_str, trim_str, position
         )
     };
 
@@ -489,6 +1109,34 @@ fn render_help_overlay(frame: &mut Frame, area: ratatui::layout::Rect) {
             ),
             Span::raw("Toggle mute"),
         ]),
+        Line::from(vec![
+            Span::styled(
+                " , / .      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Shift balance left / right"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " M          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Toggle mono downmix"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " x          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Swap left / right channels"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " t          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Toggle silence trimming"),
+        ]),
         Line::from(vec![
             Span::styled(
                 " z          ",
@@ -496,6 +1144,20 @@ fn render_help_overlay(frame: &mut Frame, area: ratatui::layout::Rect) {
             ),
             Span::raw("Toggle shuffle"),
         ]),
+        Line::from(vec![
+            Span::styled(
+                " Z          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Cycle shuffle strategy"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " i          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Toggle radio mode"),
+        ]),
         Line::from(vec![
             Span::styled(
                 " r          ",
@@ -505,10 +1167,31 @@ fn render_help_overlay(frame: &mut Frame, area: ratatui::layout::Rect) {
         ]),
         Line::from(vec![
             Span::styled(
-                " o          ",
+                " c          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Stop after current track"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " T          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Stop after N tracks"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " o + key    ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Sort by column (again to reverse)"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " O + key    ",
                 Style::default().fg(Color::Rgb(255, 200, 100)),
             ),
-            Span::raw("Cycle sort mode"),
+            Span::raw("Add column to the sort"),
         ]),
         Line::from(vec![
             Span::styled(
@@ -517,6 +1200,258 @@ fn render_help_overlay(frame: &mut Frame, area: ratatui::layout::Rect) {
             ),
             Span::raw("Toggle lyrics"),
         ]),
+        Line::from(vec![
+            Span::styled(
+                " u          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Undo queue/playlist edit"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " Ctrl-R     ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Redo"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " H          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Listening statistics"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " * 0-5      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Rate selected track (playlist view)"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " f          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Toggle favorite"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " { / }      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Focus previous / next column"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " < / >      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Narrow / widen focused column"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " v          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Start / end visual range (playlist, queue)"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " V          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Mark / unmark track"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " Esc        ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Clear marks"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " /          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Search library (n / N: next / previous match)"),
+        ]),
+        Line::from(""),
+        Line::from(vec![Span::styled(
+            " Library",
+            Style::default()
+                .add_modifier(Modifier::UNDERLINED)
+                .fg(Color::Rgb(150, 255, 150)),
+        )]),
+        Line::from(vec![
+            Span::styled(
+                " h / l      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Previous / next column (library view)"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " Enter      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Play artist, album or album from track"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " a / A      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Queue / play next the whole selection"),
+        ]),
+        Line::from(""),
+        Line::from(vec![Span::styled(
+            " Folders",
+            Style::default()
+                .add_modifier(Modifier::UNDERLINED)
+                .fg(Color::Rgb(150, 255, 150)),
+        )]),
+        Line::from(vec![
+            Span::styled(
+                " Enter      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Expand folder / play folder from file"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " l / h      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Expand / collapse folder"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " P          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Play folder recursively"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " a / A      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Queue / play next folder or file"),
+        ]),
+        Line::from(""),
+        Line::from(vec![Span::styled(
+            " Queue",
+            Style::default()
+                .add_modifier(Modifier::UNDERLINED)
+                .fg(Color::Rgb(150, 255, 150)),
+        )]),
+        Line::from(vec![
+            Span::styled(
+                " Tab        ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Cycle playlist / library / queue / playlists"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " a          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Add selected track to queue"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " A          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Play selected track next"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " C          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Clear queue"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " K / J      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Move queued track up / down (queue view)"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " d / Del    ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Remove queued track (queue view)"),
+        ]),
+        Line::from(""),
+        Line::from(vec![Span::styled(
+            " Playlists",
+            Style::default()
+                .add_modifier(Modifier::UNDERLINED)
+                .fg(Color::Rgb(150, 255, 150)),
+        )]),
+        Line::from(vec![
+            Span::styled(
+                " p          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Add selected track to highlighted playlist"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " n          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("New playlist (playlists view)"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " S          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("New smart playlist from rules"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " R / D      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Rename / delete playlist"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " I / E      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Import / export M3U, PLS or XSPF playlist"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " Enter      ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Open playlist / play from entry"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " P          ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Play playlist"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                " Esc        ",
+                Style::default().fg(Color::Rgb(255, 200, 100)),
+            ),
+            Span::raw("Back to playlist list"),
+        ]),
         Line::from(""),
         Line::from(vec![
             Span::styled(
@@ -549,6 +1484,126 @@ fn render_help_overlay(frame: &mut Frame, area: ratatui::layout::Rect) {
     frame.render_widget(paragraph, area);
 }
 
+fn render_stats_overlay(frame: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Statistics [p] Period [Enter] Replay [H] Close ")
+        .style(Style::default().bg(Color::Rgb(20, 20, 40)))
+        .border_style(Style::default().fg(Color::Rgb(100, 150, 255)))
+        .border_type(ratatui::widgets::BorderType::Rounded);
+    let inner = block.inner(area);
+    frame.render_widget(ratatui::widgets::Clear, area);
+    frame.render_widget(block, area);
+
+    let Some(stats) = &app.stats else {
+        return;
+    };
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2),
+            Constraint::Length(history::TOP_COUNT as u16 + 2),
+            Constraint::Min(3),
+        ])
+        .split(inner);
+
+    let secs = stats.listened_secs;
+    let summary = Line::from(vec![
+        Span::styled(
+            format!(" {} ", stats.period.label()),
+            Style::default()
+                .fg(Color::Rgb(100, 200, 255))
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            format!(
+                " {} plays, {} skipped, {}:{:02}:{:02} listened",
+                stats.plays,
+                stats.skips,
+                secs / 3600,
+                (secs % 3600) / 60,
+                secs % 60
+            ),
+            Style::default().fg(Color::Rgb(220, 220, 220)),
+        ),
+    ]);
+    frame.render_widget(Paragraph::new(summary), rows[0]);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(30),
+            Constraint::Percentage(35),
+            Constraint::Percentage(35),
+        ])
+        .split(rows[1]);
+
+    let top_list = |entries: &[(String, usize)], title: &'static str| {
+        let items: Vec<ListItem> = entries
+            .iter()
+            .map(|(name, count)| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("{:>4} ", count),
+                        Style::default().fg(Color::Rgb(255, 200, 100)),
+                    ),
+                    Span::styled(name.clone(), Style::default().fg(Color::Rgb(200, 200, 200))),
+                ]))
+            })
+            .collect();
+        List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(Style::default().fg(Color::Rgb(100, 150, 255)))
+                .border_type(ratatui::widgets::BorderType::Rounded),
+        )
+    };
+    frame.render_widget(top_list(&stats.top_artists, " Top Artists "), columns[0]);
+    frame.render_widget(top_list(&stats.top_albums, " Top Albums "), columns[1]);
+    frame.render_widget(top_list(&stats.top_tracks, " Top Tracks "), columns[2]);
+
+    let recent: Vec<ListItem> = app
+        .history
+        .recent()
+        .map(|record| {
+            let style = if record.skipped {
+                Style::default().fg(Color::Rgb(130, 130, 130))
+            } else {
+                Style::default().fg(Color::Rgb(200, 200, 200))
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:>9} ", history::format_age(record.started_at)),
+                    Style::default().fg(Color::Rgb(150, 150, 200)),
+                ),
+                Span::styled(record.display_name(), style),
+                Span::styled(
+                    if record.skipped { " (skipped)" } else { "" },
+                    Style::default().fg(Color::Rgb(130, 130, 130)),
+                ),
+            ]))
+        })
+        .collect();
+    let recent = List::new(recent)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Recently Played ")
+                .border_style(Style::default().fg(Color::Rgb(255, 200, 100)))
+                .border_type(ratatui::widgets::BorderType::Rounded),
+        )
+        .highlight_symbol("▸ ")
+        .highlight_spacing(HighlightSpacing::Always)
+        .highlight_style(
+            Style::default()
+                .fg(Color::Rgb(255, 200, 100))
+                .add_modifier(Modifier::BOLD),
+        );
+    frame.render_stateful_widget(recent, rows[2], &mut app.history_state);
+}
+
 fn render_status_overlay(frame: &mut Frame, msg: &str, area: ratatui::layout::Rect) {
     let block = Block::default()
         .borders(Borders::ALL)
@@ -0,0 +1,284 @@
+use crate::playlist::Playlist;
+
+/// Most edits kept for undo.
+const MAX_UNDO: usize = 100;
+
+/// Undo and redo stacks of described snapshots.
+pub struct UndoStack<T> {
+    undo: Vec<(String, T)>,
+    redo: Vec<(String, T)>,
+}
+
+impl<T> Default for UndoStack<T> {
+    fn default() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+}
+
+impl<T> UndoStack<T> {
+    /// Remembers the state from before an edit. A new edit discards the redo history.
+    pub fn record(&mut self, description: &str, before: T) {
+        self.undo.push((description.to_string(), before));
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// The snapshot `undo` would restore, so the current state can be
+    /// captured to match it.
+    pub fn next_undo(&self) -> Option<&T> {
+        self.undo.last().map(|(_, snapshot)| snapshot)
+    }
+
+    /// The snapshot `redo` would restore.
+    pub fn next_redo(&self) -> Option<&T> {
+        self.redo.last().map(|(_, snapshot)| snapshot)
+    }
+
+    /// Swaps `current` for the state before the last edit.
+    pub fn undo(&mut self, current: T) -> Option<(String, T)> {
+        let (description, before) = self.undo.pop()?;
+        self.redo.push((description.clone(), current));
+        Some((description, before))
+    }
+
+    /// Swaps `current` for the state the last undo left.
+    pub fn redo(&mut self, current: T) -> Option<(String, T)> {
+        let (description, after) = self.redo.pop()?;
+        self.undo.push((description.clone(), current));
+        Some((description, after))
+    }
+}
+
+/// The playlists an edit touched, by name, as they were at the time;
+/// `None` for a name that had no playlist. Other playlists are left out
+/// so snapshots stay small.
+#[derive(Default)]
+pub struct PlaylistSnapshot(Vec<(String, Option<Playlist>)>);
+
+impl PlaylistSnapshot {
+    pub fn take(playlists: &[Playlist], names: &[String]) -> Self {
+        Self(
+            names
+                .iter()
+                .enumerate()
+                .filter(|&(i, name)| !names[..i].contains(name))
+                .map(|(_, name)| {
+                    let playlist = playlists.iter().find(|p| p.name == *name).cloned();
+                    (name.clone(), playlist)
+                })
+                .collect(),
+        )
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.0.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Puts the snapshotted playlists back into `playlists`, kept sorted by
+    /// name, and returns the ones they replaced.
+    pub fn restore(self, playlists: &mut Vec<Playlist>) -> Vec<Playlist> {
+        let mut replaced = Vec::new();
+        for (name, _) in &self.0 {
+            if let Some(pos) = playlists.iter().position(|p| p.name == *name) {
+                replaced.push(playlists.remove(pos));
+            }
+        }
+        for playlist in self.0.into_iter().filter_map(|(_, playlist)| playlist) {
+            let name = playlist.name.to_lowercase();
+            let pos = playlists
+                .iter()
+                .position(|p| p.name.to_lowercase() > name)
+                .unwrap_or(playlists.len());
+            playlists.insert(pos, playlist);
+        }
+        replaced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Stand-in for the app state: a queue and the playlists.
+    struct State {
+        queue: Vec<u32>,
+        playlists: Vec<Playlist>,
+    }
+
+    struct Snapshot {
+        queue: Vec<u32>,
+        playlists: PlaylistSnapshot,
+    }
+
+    impl State {
+        fn new() -> Self {
+            Self {
+                queue: vec![1, 2, 3],
+                playlists: vec![playlist("Chill", &["a"]), playlist("Mix", &["b", "c"])],
+            }
+        }
+
+        fn snapshot(&self, playlists: &[String]) -> Snapshot {
+            Snapshot {
+                queue: self.queue.clone(),
+                playlists: PlaylistSnapshot::take(&self.playlists, playlists),
+            }
+        }
+
+        fn restore(&mut self, snapshot: Snapshot) -> Vec<Playlist> {
+            self.queue = snapshot.queue;
+            snapshot.playlists.restore(&mut self.playlists)
+        }
+
+        fn undo(&mut self, edits: &mut UndoStack<Snapshot>) -> Option<String> {
+            let names = edits.next_undo()?.playlists.names();
+            let current = self.snapshot(&names);
+            let (description, snapshot) = edits.undo(current)?;
+            self.restore(snapshot);
+            Some(description)
+        }
+
+        fn redo(&mut self, edits: &mut UndoStack<Snapshot>) -> Option<String> {
+            let names = edits.next_redo()?.playlists.names();
+            let current = self.snapshot(&names);
+            let (description, snapshot) = edits.redo(current)?;
+            self.restore(snapshot);
+            Some(description)
+        }
+
+        fn names(&self) -> Vec<&str> {
+            self.playlists.iter().map(|p| p.name.as_str()).collect()
+        }
+    }
+
+    fn playlist(name: &str, entries: &[&str]) -> Playlist {
+        let mut playlist = Playlist::new(name);
+        playlist.entries = entries.iter().map(PathBuf::from).collect();
+        playlist
+    }
+
+    #[test]
+    fn undoes_and_redoes_a_queue_edit() {
+        let mut state = State::new();
+        let mut edits = UndoStack::default();
+
+        edits.record("remove from queue", state.snapshot(&[]));
+        state.queue.remove(1);
+        assert!(edits.next_undo().unwrap().playlists.0.is_empty());
+
+        assert_eq!(state.undo(&mut edits).as_deref(), Some("remove from queue"));
+        assert_eq!(state.queue, [1, 2, 3]);
+        assert!(state.undo(&mut edits).is_none());
+
+        assert_eq!(state.redo(&mut edits).as_deref(), Some("remove from queue"));
+        assert_eq!(state.queue, [1, 3]);
+        assert!(state.redo(&mut edits).is_none());
+        assert_eq!(state.names(), ["Chill", "Mix"]);
+    }
+
+    #[test]
+    fn undoes_and_redoes_playlist_edits_touching_only_those_playlists() {
+        let mut state = State::new();
+        let mut edits = UndoStack::default();
+
+        edits.record("add to playlist", state.snapshot(&["Mix".to_string()]));
+        state.playlists[1].entries.push(PathBuf::from("d"));
+        // Only the edited playlist is kept in the snapshot.
+        assert_eq!(edits.next_undo().unwrap().playlists.names(), ["Mix"]);
+
+        edits.record(
+            "rename playlist",
+            state.snapshot(&["Chill".to_string(), "Ambient".to_string()]),
+        );
+        let mut renamed = state.playlists.remove(0);
+        renamed.name = "Ambient".to_string();
+        state.playlists.insert(0, renamed);
+
+        assert_eq!(state.undo(&mut edits).as_deref(), Some("rename playlist"));
+        assert_eq!(state.names(), ["Chill", "Mix"]);
+        assert_eq!(state.playlists[1].entries.len(), 3);
+
+        assert_eq!(state.undo(&mut edits).as_deref(), Some("add to playlist"));
+        assert!(state.playlists[1] == playlist("Mix", &["b", "c"]));
+
+        assert_eq!(state.redo(&mut edits).as_deref(), Some("add to playlist"));
+        assert!(state.playlists[1] == playlist("Mix", &["b", "c", "d"]));
+        assert_eq!(state.redo(&mut edits).as_deref(), Some("rename playlist"));
+        assert_eq!(state.names(), ["Ambient", "Mix"]);
+        assert!(state.playlists[0] == playlist("Ambient", &["a"]));
+
+        // Renaming to the same name touches one playlist, not two.
+        let names = ["Mix".to_string(), "Mix".to_string()];
+        edits.record("rename playlist", state.snapshot(&names));
+        state.undo(&mut edits);
+        assert_eq!(state.names(), ["Ambient", "Mix"]);
+    }
+
+    #[test]
+    fn undoes_creating_and_deleting_playlists() {
+        let mut state = State::new();
+        let mut edits = UndoStack::default();
+
+        edits.record("new playlist", state.snapshot(&["Fresh".to_string()]));
+        state.playlists.insert(1, playlist("Fresh", &[]));
+        edits.record("delete playlist", state.snapshot(&["Mix".to_string()]));
+        state.playlists.pop();
+
+        state.undo(&mut edits);
+        assert_eq!(state.names(), ["Chill", "Fresh", "Mix"]);
+        let replaced = {
+            let names = edits.next_undo().unwrap().playlists.names();
+            let current = state.snapshot(&names);
+            let (_, snapshot) = edits.undo(current).unwrap();
+            state.restore(snapshot)
+        };
+        assert_eq!(state.names(), ["Chill", "Mix"]);
+        // The app deletes the files of playlists that were taken out.
+        assert!(replaced == [playlist("Fresh", &[])]);
+    }
+
+    #[test]
+    fn a_new_edit_discards_the_redo_branch() {
+        let mut state = State::new();
+        let mut edits = UndoStack::default();
+
+        edits.record("add to queue", state.snapshot(&[]));
+        state.queue.push(4);
+        state.undo(&mut edits);
+        assert!(edits.next_redo().is_some());
+
+        edits.record("remove from playlist", state.snapshot(&["Mix".to_string()]));
+        state.playlists[1].entries.remove(0);
+        assert!(edits.next_redo().is_none());
+        assert!(state.redo(&mut edits).is_none());
+
+        assert_eq!(
+            state.undo(&mut edits).as_deref(),
+            Some("remove from playlist")
+        );
+        assert_eq!(state.playlists[1].entries.len(), 2);
+        assert_eq!(state.queue, [1, 2, 3]);
+        assert!(state.undo(&mut edits).is_none());
+    }
+
+    #[test]
+    fn keeps_at_most_max_undo_edits() {
+        let mut edits = UndoStack::default();
+        for i in 0..MAX_UNDO + 5 {
+            edits.record("edit", i);
+        }
+        let mut undone = Vec::new();
+        while let Some((_, before)) = edits.undo(0) {
+            undone.push(before);
+        }
+        assert_eq!(undone.len(), MAX_UNDO);
+        assert_eq!(undone.last(), Some(&5));
+    }
+}